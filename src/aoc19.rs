@@ -1,142 +1,499 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 
-fn start_col(row: &Vec<char>) -> usize {
-    row.iter().enumerate().find(|c| *c.1 == '|').unwrap().0
+use grid::{Direction, Grid, Pos};
+
+type Map = Grid<char>;
+
+#[derive(Debug, PartialEq)]
+enum RouteError {
+    NoEntry,
+    AmbiguousEntry(Vec<Pos>),
+    AmbiguousJunction(Pos),
+    DeadEnd(Pos),
+    UnknownCell(Pos, char),
+    Loop(Pos),
 }
 
-#[derive(Debug, Clone)]
-enum Direction {
-    Up, 
-    Down,
-    Left, 
-    Right
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RouteError::NoEntry => write!(f, "no entry on any edge"),
+            RouteError::AmbiguousEntry(ref ps) => {
+                write!(f, "several entries:")?;
+                for p in ps {
+                    write!(f, " ({}, {})", p.row, p.col)?;
+                }
+                Ok(())
+            },
+            RouteError::AmbiguousJunction(p) => write!(f, "ambiguous junction at ({}, {})", p.row, p.col),
+            RouteError::DeadEnd(p) => write!(f, "dead end at ({}, {})", p.row, p.col),
+            RouteError::UnknownCell(p, c) => write!(f, "unknown cell {:?} at ({}, {})", c, p.row, p.col),
+            RouteError::Loop(p) => write!(f, "route loops back at ({}, {})", p.row, p.col),
+        }
+    }
 }
 
-impl Direction {
-    fn delta(&self) -> (isize, isize) {
-        match self {
-            Direction::Down => (0, 1), 
-            Direction::Up => (0, -1),
-            Direction::Left => (-1, 0),
-            Direction::Right => (1, 0),
+impl RouteError {
+    fn pos(&self) -> Option<Pos> {
+        match *self {
+            RouteError::AmbiguousJunction(p) | RouteError::DeadEnd(p) | RouteError::UnknownCell(p, _)
+                | RouteError::Loop(p) => Some(p),
+            _ => None,
         }
     }
+}
+
+fn is_vertical(dir: Direction) -> bool {
+    dir == Direction::Up || dir == Direction::Down
+}
+
+// Whether a tube going in `dir` can continue on a cell holding `c` after a
+// turn. Going straight, tubes also cross each other.
+fn fits(c: char, dir: Direction) -> bool {
+    match c {
+        '|' => is_vertical(dir),
+        '-' => !is_vertical(dir),
+        '+' => true,
+        c => c.is_uppercase(),
+    }
+}
 
-    fn turn_left(&self) -> Self {
-        match self {
-            Direction::Up => Direction::Left,
-            Direction::Down => Direction::Right,
-            Direction::Left => Direction::Down,
-            Direction::Right => Direction::Up,
+// Straight tubes ending on the edge of the map and pointing into it.
+fn find_entry(map: &Map) -> Result<(Pos, Direction), RouteError> {
+    let mut entries = Vec::new();
+    for p in map.positions() {
+        if map[p] != '|' && map[p] != '-' {
+            continue;
+        }
+        for &dir in Direction::all().iter() {
+            let outside = p.step(dir.turn_around());
+            if !map.contains(outside) && fits(map[p], dir) && State::can_go(p.step(dir), map) {
+                entries.push((p, dir));
+            }
         }
     }
+    // A route that ends on an edge tube makes that tube look like an entry
+    // too. Drop such far ends; when two candidates lead to each other, the
+    // one coming down from the top row is the start.
+    let ends: Vec<Option<Pos>> = entries.iter().map(|&(p, dir)| route_end(map, p, dir)).collect();
+    let from_top = |&(p, dir): &(Pos, Direction)| p.row == 0 && dir == Direction::Down;
+    let starts: Vec<(Pos, Direction)> = (0..entries.len())
+        .filter(|&j| !(0..entries.len()).any(|i| {
+            i != j && ends[i] == Some(entries[j].0)
+                && (ends[j] != Some(entries[i].0) || (from_top(&entries[i]) && !from_top(&entries[j])))
+        }))
+        .map(|j| entries[j])
+        .collect();
+    match starts.len() {
+        0 => Err(RouteError::NoEntry),
+        1 => Ok(starts[0]),
+        _ => Err(RouteError::AmbiguousEntry(starts.iter().map(|e| e.0).collect())),
+    }
+}
 
-    fn turn_right(&self) -> Self {
-        match self {
-            Direction::Up => Direction::Right,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-            Direction::Right => Direction::Down,
+// Where a route entering at `pos` comes to a clean stop, if it does.
+fn route_end(map: &Map, pos: Pos, dir: Direction) -> Option<Pos> {
+    let mut state = State::new(pos, dir, map);
+    loop {
+        match state.step(map) {
+            Ok(true) => {},
+            Ok(false) => return Some(state.pos),
+            Err(_) => return None,
         }
     }
 }
 
 #[derive(Debug)]
 struct State {
-    row: usize,
-    col: usize,
+    pos: Pos,
     dir: Direction,
     letters: String,
-    steps: usize
-}
-
-type Map = Vec<Vec<char>>;
-
-fn add(u: usize, i: isize) -> usize {
-    (u as isize + i) as usize
+    steps: usize,
+    // (position, direction) pairs so far; coming back to one means a loop
+    seen: HashSet<(Pos, Direction)>,
 }
 
 impl State {
-    fn new(col: usize) -> Self {
+    fn new(pos: Pos, dir: Direction, map: &Map) -> Self {
+        let mut letters = String::new();
+        if map[pos].is_uppercase() {
+            letters.push(map[pos]);
+        }
         State {
-            row: 0,
-            col: col,
-            dir: Direction::Down,
-            letters: String::new(),
+            pos,
+            dir,
+            letters,
             steps: 1,
+            seen: [(pos, dir)].iter().cloned().collect(),
         }
     }
 
-    fn step(&mut self, map: &Map) -> bool {
-        if !self.next_step(map) {
-            return false
+    fn step(&mut self, map: &Map) -> Result<bool, RouteError> {
+        if !self.next_step(map)? {
+            return Ok(false)
+        }
+        if !self.seen.insert((self.pos, self.dir)) {
+            return Err(RouteError::Loop(self.pos));
         }
 
-        let c_at_p = map[self.row][self.col];
+        let c_at_p = map[self.pos];
         if c_at_p.is_uppercase() {
             self.letters.push(c_at_p)
         }
 
         self.steps+=1;
 
-        true
+        Ok(true)
+    }
+
+    // Straight on where possible, except at '+' which always turns. Letters
+    // may sit on corners too.
+    fn next_step(&mut self, map: &Map) -> Result<bool, RouteError> {
+        let here = map[self.pos];
+        match here {
+            '|' | '-' | '+' => (),
+            c if c.is_uppercase() => (),
+            c => return Err(RouteError::UnknownCell(self.pos, c)),
+        }
+
+        let straight = self.pos.step(self.dir);
+        if here != '+' && State::can_go(straight, map) {
+            self.pos = straight;
+            return Ok(true)
+        }
+        if here == '|' || here == '-' {
+            return Ok(false)
+        }
+
+        let turns: Vec<Direction> = [self.dir.turn_right(), self.dir.turn_left()].iter().cloned()
+            .filter(|&d| map.get(self.pos.step(d)).is_some_and(|&c| fits(c, d)))
+            .collect();
+        match turns.len() {
+            0 if here == '+' => Err(RouteError::DeadEnd(self.pos)),
+            0 => Ok(false),
+            1 => {
+                self.dir = turns[0];
+                self.pos = self.pos.step(self.dir);
+                Ok(true)
+            },
+            _ => Err(RouteError::AmbiguousJunction(self.pos)),
+        }
+    }
+
+    fn can_go(pos: Pos, map: &Map) -> bool {
+        map.get(pos).is_some_and(|&c| c != ' ')
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Edge {
+    a: usize,
+    b: usize,
+    len: usize,
+}
+
+// Junctions, letters and tube ends are nodes, the tubes between them edges
+// weighted by their number of steps. Crossings are cells where two tubes pass
+// straight through each other.
+#[derive(Debug)]
+struct Graph {
+    nodes: Vec<Pos>,
+    edges: Vec<Edge>,
+    crossings: Vec<Pos>,
+    index: HashMap<Pos, usize>,
+}
+
+fn is_crossing(map: &Map, p: Pos) -> bool {
+    (map[p] == '|' || map[p] == '-')
+        && Direction::all().iter().all(|&d| map.get(p.step(d)).is_some_and(|&c| fits(c, d)))
+}
+
+fn is_node(map: &Map, p: Pos) -> bool {
+    let open = |d: Direction| State::can_go(p.step(d), map);
+    match map[p] {
+        ' ' => false,
+        '|' => !(open(Direction::Up) && open(Direction::Down)),
+        '-' => !(open(Direction::Left) && open(Direction::Right)),
+        _ => true,
     }
+}
 
-    fn next_step(&mut self, map: &Map) -> bool {
-        let (dc, dr) = self.dir.delta();
-        if State::can_go(add(self.row, dr), add(self.col, dc), map) {
-            self.row = add(self.row, dr);
-            self.col = add(self.col, dc);
-            return true
+impl Graph {
+    fn new(map: &Map) -> Self {
+        let mut g = Graph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            crossings: Vec::new(),
+            index: HashMap::new(),
+        };
+        for p in map.positions() {
+            if is_node(map, p) {
+                g.add_node(p);
+            }
+            else if is_crossing(map, p) {
+                g.crossings.push(p);
+            }
         }
 
-        let old_dir = self.dir.clone();
+        let mut used = HashSet::new();
+        for n in 0..g.nodes.len() {
+            let p = g.nodes[n];
+            for &d in Direction::all().iter() {
+                let q = p.step(d);
+                let port = match map[p] {
+                    '|' | '-' => fits(map[p], d),
+                    _ => true,
+                };
+                if !port || !State::can_go(q, map) || !(fits(map[q], d) || is_crossing(map, q)) {
+                    continue;
+                }
+                if !used.insert((p, d)) {
+                    continue;
+                }
 
-        self.dir = old_dir.turn_right();
-        let (dc, dr) = self.dir.delta();
-        if State::can_go(add(self.row, dr), add(self.col, dc), map) {
-            self.row = add(self.row, dr);
-            self.col = add(self.col, dc);
-            return true
+                let mut end = q;
+                let mut len = 1;
+                while !is_node(map, end) && State::can_go(end.step(d), map) {
+                    end = end.step(d);
+                    len += 1;
+                }
+                used.insert((end, d.turn_around()));
+                let b = g.add_node(end);
+                g.edges.push(Edge { a: n, b, len });
+            }
         }
+        g
+    }
+
+    fn add_node(&mut self, p: Pos) -> usize {
+        if let Some(&i) = self.index.get(&p) {
+            return i;
+        }
+        self.nodes.push(p);
+        self.index.insert(p, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn letter(&self, map: &Map, c: char) -> Option<usize> {
+        self.nodes.iter().position(|&p| map[p] == c)
+    }
+
+    fn neighbours(&self, n: usize) -> Vec<(usize, usize)> {
+        self.edges.iter().filter_map(|e| {
+            if e.a == n {
+                Some((e.b, e.len))
+            }
+            else if e.b == n {
+                Some((e.a, e.len))
+            }
+            else {
+                None
+            }
+        }).collect()
+    }
+
+    // Length of the shortest path along the tubes and the nodes on it.
+    fn shortest_path(&self, from: usize, to: usize) -> Option<(usize, Vec<Pos>)> {
+        let mut dist = vec![usize::MAX; self.nodes.len()];
+        let mut prev = vec![None; self.nodes.len()];
+        let mut queue = BinaryHeap::new();
+        dist[from] = 0;
+        queue.push(Reverse((0, from)));
+        while let Some(Reverse((d, n))) = queue.pop() {
+            if n == to {
+                let mut path = vec![self.nodes[n]];
+                let mut cur = n;
+                while let Some(p) = prev[cur] {
+                    path.push(self.nodes[p]);
+                    cur = p;
+                }
+                path.reverse();
+                return Some((d, path));
+            }
+            if d > dist[n] {
+                continue;
+            }
+            for (m, len) in self.neighbours(n) {
+                if d + len < dist[m] {
+                    dist[m] = d + len;
+                    prev[m] = Some(n);
+                    queue.push(Reverse((d + len, m)));
+                }
+            }
+        }
+        None
+    }
+
+    // Edges that can't be reached from the node `from`.
+    fn unreachable(&self, from: usize) -> Vec<&Edge> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut todo = vec![from];
+        seen[from] = true;
+        while let Some(n) = todo.pop() {
+            for (m, _) in self.neighbours(n) {
+                if !seen[m] {
+                    seen[m] = true;
+                    todo.push(m);
+                }
+            }
+        }
+        self.edges.iter().filter(|e| !seen[e.a]).collect()
+    }
+}
 
-        self.dir = old_dir.turn_left();
-        let (dc, dr) = self.dir.delta();
-        if State::can_go(add(self.row, dr), add(self.col, dc), map) {
-            self.row = add(self.row, dr);
-            self.col = add(self.col, dc);
-            return true
+// Follows the route as far as it goes, keeping the step at which each cell was
+// first reached.
+fn walk(vmap: &Map) -> (HashMap<Pos, usize>, Option<State>, Option<RouteError>) {
+    let mut steps = HashMap::new();
+    let (pos, dir) = match find_entry(vmap) {
+        Ok(entry) => entry,
+        Err(e) => return (steps, None, Some(e)),
+    };
+    let mut state = State::new(pos, dir, vmap);
+    steps.insert(pos, state.steps);
+    loop {
+        match state.step(vmap) {
+            Ok(true) => {
+                steps.entry(state.pos).or_insert(state.steps);
+            },
+            Ok(false) => return (steps, Some(state), None),
+            Err(e) => return (steps, Some(state), Some(e)),
         }
+    }
+}
 
-        // println!("Nowhere to go {}, {}", self.row, self.col);
+const PATH: &str = "\x1b[32m";
+const LETTER: &str = "\x1b[1;33m";
+const FAULT: &str = "\x1b[1;41m";
+const RESET: &str = "\x1b[0m";
 
-        false
+// The diagram with the route drawn over it: in colour, or with '*' for tubes
+// and '!' where the route broke down in plain text. Each row is followed by the
+// step numbers of the letters on it, and the result comes last.
+fn overlay(map: &str, colour: bool) -> String {
+    let vmap = Map::parse(map, ' ', |c| c);
+    let (steps, state, error) = walk(&vmap);
+    let fault = error.as_ref().and_then(|e| e.pos());
+
+    let mut out = String::new();
+    for row in 0..vmap.height() {
+        let cols: Vec<Pos> = (0..vmap.width()).map(|col| Pos::new(row as isize, col as isize)).collect();
+        let end = cols.iter().rposition(|&p| vmap[p] != ' ').map_or(0, |i| i + 1);
+        let mut notes = Vec::new();
+        for &p in &cols[..end] {
+            let c = vmap[p];
+            let (style, shown) = if Some(p) == fault {
+                (FAULT, '!')
+            }
+            else if let Some(n) = steps.get(&p) {
+                if c.is_uppercase() {
+                    notes.push(format!("{}:{}", c, n));
+                    (LETTER, c)
+                }
+                else {
+                    (PATH, '*')
+                }
+            }
+            else {
+                ("", c)
+            };
+            if !colour {
+                out.push(shown);
+            }
+            else if style.is_empty() {
+                out.push(c);
+            }
+            else {
+                out.push_str(style);
+                out.push(c);
+                out.push_str(RESET);
+            }
+        }
+        if !notes.is_empty() {
+            out.push_str("   ");
+            out.push_str(&notes.join(" "));
+        }
+        out.push('\n');
     }
 
-    fn can_go(row: usize, col: usize, map: &Map) -> bool {
-        row < map.len() && col < map[row].len() && map[row][col] != ' '
+    let (letters, n) = state.map_or((String::new(), 0), |s| (s.letters, s.steps));
+    match error {
+        None => out.push_str(&format!("{:?}\n", (letters, n))),
+        Some(e) => out.push_str(&format!("{:?} then {}\n", (letters, n), e)),
     }
+    out
 }
 
 pub fn run() {
+    let args: Vec<String> = env::args().skip(2).collect();
+    if args.first().map(|s| s.as_str()) == Some("overlay") {
+        let name = args.get(1).map_or("day19.txt", |s| s.as_str());
+        let mut map = String::new();
+        File::open(name).unwrap().read_to_string(&mut map).unwrap();
+        print!("{}", overlay(&map, args.get(2).map(|s| s.as_str()) == Some("colour")));
+        return;
+    }
+
     let mut file = File::open("day19.txt").unwrap();
     let mut map = String::new();
     file.read_to_string(&mut map).unwrap();
-    println!("1: {:?}", run_1(&map));
+
+    match args.first().map(|s| s.as_str()) {
+        Some("graph") => {
+            let vmap = Map::parse(&map, ' ', |c| c);
+            let g = Graph::new(&vmap);
+            let junctions = g.nodes.iter().filter(|&&p| vmap[p] == '+').count();
+            println!("nodes: {} junctions: {} crossings: {} segments: {}",
+                     g.nodes.len(), junctions, g.crossings.len(), g.edges.len());
+            if let Ok((entry, _)) = find_entry(&vmap) {
+                for e in g.unreachable(g.index[&entry]) {
+                    let (a, b) = (g.nodes[e.a], g.nodes[e.b]);
+                    println!("unreachable: ({}, {}) - ({}, {}) length {}", a.row, a.col, b.row, b.col, e.len);
+                }
+            }
+        },
+        Some("path") => {
+            let vmap = Map::parse(&map, ' ', |c| c);
+            let g = Graph::new(&vmap);
+            let (from, to) = match (args.get(1), args.get(2)) {
+                (Some(from), Some(to)) => (from, to),
+                _ => return println!("usage: 19 [graph] [path FROM TO] [overlay [FILE] [colour]]"),
+            };
+            let letter = |s: &String| g.letter(&vmap, s.chars().next().unwrap()).expect("no such letter");
+            match g.shortest_path(letter(from), letter(to)) {
+                Some((len, path)) => {
+                    let letters: String = path.iter().map(|&p| vmap[p]).filter(|c| c.is_uppercase()).collect();
+                    println!("{} -> {}: {} steps via {}", from, to, len, letters);
+                },
+                None => println!("{} -> {}: no path", from, to),
+            }
+        },
+        _ => {
+            match run_1(&map) {
+                Ok(r) => println!("1: {:?}", r),
+                Err(e) => println!("1: {}", e),
+            }
+        }
+    }
 }
 
-fn run_1(map: &str) -> (String, usize) {
-    let vmap:Map = map.lines().map(|s| s.chars().collect()).collect();
-    let mut state = State::new(start_col(&vmap[0]));
+fn run_1(map: &str) -> Result<(String, usize), RouteError> {
+    let vmap = Map::parse(map, ' ', |c| c);
+    let (pos, dir) = find_entry(&vmap)?;
+    let mut state = State::new(pos, dir, &vmap);
     loop {
         // println!("State: {:?}", state);
-        if !state.step(&vmap) {
+        if !state.step(&vmap)? {
             break;
         }
     }
     //16099
-    (state.letters, state.steps)
+    Ok((state.letters, state.steps))
 }
 
 
@@ -152,7 +509,7 @@ r"       |
    F---|----E|--+ 
        |  |  |  D 
        +B-+  +--+";
-        let (letters, _) = run_1(input);
+        let (letters, _) = run_1(input).unwrap();
         assert_eq!("ABCDEF", letters);
     }
 
@@ -165,7 +522,126 @@ r"       |
    F---|----E|--+ 
        |  |  |  D 
        +B-+  +--+";
-        let (_, steps) = run_1(input);
+        let (_, steps) = run_1(input).unwrap();
         assert_eq!(38, steps);
     }
+
+    #[test]
+    fn aoc19_entries() {
+        let left = "-A-+\n   |\n   B";
+        assert_eq!(Ok(("AB".to_string(), 6)), run_1(left));
+
+        let right = "  +--\n  |  \n  C  ";
+        assert_eq!(Ok(("C".to_string(), 5)), run_1(right));
+
+        let bottom = " D+\n  |\n  |";
+        assert_eq!(Ok(("D".to_string(), 4)), run_1(bottom));
+
+        assert_eq!(Err(RouteError::NoEntry), run_1(" \n +-+\n"));
+        assert_eq!(Err(RouteError::AmbiguousEntry(vec![Pos::new(0, 0), Pos::new(0, 2)])), run_1("| |\n+-+"));
+
+        // the route ends on an edge tube, which is not a second entry
+        let edge_end = "  |   \n  A   \n  +-B--\n";
+        assert_eq!(Ok(("AB".to_string(), 7)), run_1(edge_end));
+        let edge_end = "|    \n+-A--\n";
+        assert_eq!(Ok(("A".to_string(), 6)), run_1(edge_end));
+    }
+
+    #[test]
+    fn aoc19_corners() {
+        // letter on a corner, then straight across the F-G tube
+        let input = "  |\n  A-+\n   F|G\n    B";
+        assert_eq!(Ok(("AB".to_string(), 6)), run_1(input));
+
+        let input = " |\n A-+\n   |\n   C";
+        assert_eq!(Ok(("AC".to_string(), 6)), run_1(input));
+    }
+
+    #[test]
+    fn aoc19_errors() {
+        let ambiguous = "  |  \n  |  \n -+- ";
+        assert_eq!(Err(RouteError::AmbiguousJunction(Pos::new(2, 2))), run_1(ambiguous));
+        assert_eq!("ambiguous junction at (2, 2)", format!("{}", RouteError::AmbiguousJunction(Pos::new(2, 2))));
+
+        assert_eq!(Err(RouteError::DeadEnd(Pos::new(2, 1))), run_1(" |\n |\n +"));
+        assert_eq!(Err(RouteError::UnknownCell(Pos::new(1, 1), '*')), run_1(" |\n *"));
+
+        // '+' always turns, so this goes round the square for ever
+        let square = "|\n+-+\n| |\n+-+";
+        assert_eq!(Err(RouteError::Loop(Pos::new(1, 1))), run_1(square));
+        assert_eq!("route loops back at (1, 1)", RouteError::Loop(Pos::new(1, 1)).to_string());
+        assert!(overlay(square, false).ends_with("then route loops back at (1, 1)\n"));
+    }
+
+    #[test]
+    fn aoc19_graph() {
+        let input=
+r"       |          
+       |  +--+    
+       A  |  C    
+   F---|----E|--+ 
+       |  |  |  D 
+       +B-+  +--+";
+        let map = Map::parse(input, ' ', |c| c);
+        let g = Graph::new(&map);
+
+        assert_eq!(vec![Pos::new(3, 7), Pos::new(3, 10), Pos::new(3, 13)], g.crossings);
+        // entry, six letters and seven junctions
+        assert_eq!(14, g.nodes.len());
+        assert_eq!(13, g.edges.len());
+        // the route visits every cell once
+        assert_eq!(38 - 1, g.edges.iter().map(|e| e.len).sum::<usize>());
+
+        let a = g.letter(&map, 'A').unwrap();
+        let f = g.letter(&map, 'F').unwrap();
+        let (len, path) = g.shortest_path(a, f).unwrap();
+        assert_eq!(37 - 2, len);
+        assert_eq!(Pos::new(2, 7), path[0]);
+        assert_eq!(Pos::new(3, 3), *path.last().unwrap());
+        assert_eq!(Some((0, vec![Pos::new(2, 7)])), g.shortest_path(a, a));
+
+        let entry = g.index[&Pos::new(0, 7)];
+        assert!(g.unreachable(entry).is_empty());
+    }
+
+    #[test]
+    fn aoc19_unreachable() {
+        let input = " | \n A  -Z-\n";
+        let map = Map::parse(input, ' ', |c| c);
+        let g = Graph::new(&map);
+        let a = g.letter(&map, 'A').unwrap();
+        let z = g.letter(&map, 'Z').unwrap();
+        assert_eq!(None, g.shortest_path(a, z));
+
+        let unreachable = g.unreachable(g.index[&Pos::new(0, 1)]);
+        assert_eq!(2, unreachable.len());
+        assert!(unreachable.iter().all(|e| e.len == 1));
+    }
+
+    #[test]
+    fn aoc19_shortcut() {
+        let input = " |    \n A---+\n |   |\n +-B-+\n";
+        let map = Map::parse(input, ' ', |c| c);
+        let g = Graph::new(&map);
+        let a = g.letter(&map, 'A').unwrap();
+        let b = g.letter(&map, 'B').unwrap();
+        let (len, path) = g.shortest_path(a, b).unwrap();
+        assert_eq!(4, len);
+        assert_eq!(vec![Pos::new(1, 1), Pos::new(3, 1), Pos::new(3, 3)], path);
+    }
+
+    #[test]
+    fn aoc19_overlay() {
+        let input = "  |  \n  A  \n  +-B\n     \n";
+        assert_eq!("  *\n  A   A:2\n  **B   B:5\n\n(\"AB\", 5)\n", overlay(input, false));
+
+        let colour = overlay(input, true);
+        assert!(colour.starts_with("  \x1b[32m|\x1b[0m\n  \x1b[1;33mA\x1b[0m   A:2\n"));
+
+        let broken = "  |  \n  |  \n -+- \n";
+        let expected = "  *\n  *\n -!-\n(\"\", 3) then ambiguous junction at (2, 2)\n";
+        assert_eq!(expected, overlay(broken, false));
+
+        assert_eq!("\n(\"\", 0) then no entry on any edge\n", overlay("   \n", false));
+    }
 }
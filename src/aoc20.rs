@@ -1,168 +1,626 @@
-use std::ops::{Add, AddAssign};
+use std::ops::{Add, AddAssign, Index, Mul, Neg, Sub};
+use std::str::FromStr;
 use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
 use std::fs;
 
-#[derive(Debug, Clone, PartialEq)]
-struct Vec3 {
-    x: i64,
-    y: i64,
-    z: i64,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct VecN<const D: usize>([i64; D]);
+
+impl<const D: usize> VecN<D> {
+    fn manhattan(&self) -> i64 {
+        self.0.iter().map(|c| c.abs()).sum()
+    }
+
+    // The puzzle measures in Manhattan distance; this is for everything else.
+    #[allow(dead_code)]
+    fn euclidean(&self) -> f64 {
+        (self.0.iter().map(|c| c * c).sum::<i64>() as f64).sqrt()
+    }
 }
 
-impl Vec3 {
-    fn new(x: i64, y: i64, z: i64) -> Self {
-        Vec3 {
-            x: x, 
-            y: y,
-            z: z,
-        }
+impl<const D: usize> Index<usize> for VecN<D> {
+    type Output = i64;
+    fn index(&self, i: usize) -> &i64 {
+        &self.0[i]
     }
 }
 
-impl Add for Vec3 {
-    type Output = Vec3;
-    fn add(self, rhs: Self) -> Self::Output {
-        Vec3::new(self.x + rhs.x, 
-                  self.y + rhs.y,
-                  self.z + rhs.z)
+impl<const D: usize> Add for VecN<D> {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self {
+        self += rhs;
+        self
     }
 }
 
-impl AddAssign for Vec3 {
+impl<const D: usize> AddAssign for VecN<D> {
     fn add_assign(&mut self, other: Self) {
-        self.x += other.x;
-        self.y += other.y;
-        self.z += other.z;
+        for i in 0..D {
+            self.0[i] += other.0[i];
+        }
+    }
+}
+
+impl<const D: usize> Sub for VecN<D> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl<const D: usize> Mul<i64> for VecN<D> {
+    type Output = Self;
+    fn mul(mut self, k: i64) -> Self {
+        for c in self.0.iter_mut() {
+            *c *= k;
+        }
+        self
+    }
+}
+
+impl<const D: usize> Neg for VecN<D> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        self * -1
+    }
+}
+
+// "<1,-2,3>", with or without the brackets.
+impl<const D: usize> FromStr for VecN<D> {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let inner = s.trim().trim_start_matches('<').trim_end_matches('>');
+        let mut v = VecN([0; D]);
+        let mut n = 0;
+        for part in inner.split(',') {
+            if n == D {
+                return Err(format!("more than {} coordinates in {:?}", D, s));
+            }
+            v.0[n] = part.trim().parse().map_err(|_| format!("bad coordinate {:?} in {:?}", part, s))?;
+            n += 1;
+        }
+        if n < D {
+            return Err(format!("{} coordinates instead of {} in {:?}", n, D, s));
+        }
+        Ok(v)
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-struct Particle {
-    pos: Vec3,
-    vel: Vec3,
-    acc: Vec3,
+struct Particle<const D: usize = 3> {
+    pos: VecN<D>,
+    vel: VecN<D>,
+    acc: VecN<D>,
 }
 
-impl Particle {
-    fn new(p: &Vec3, v: &Vec3, a: &Vec3) -> Self {
+impl<const D: usize> Particle<D> {
+    fn new(p: &VecN<D>, v: &VecN<D>, a: &VecN<D>) -> Self {
         Particle {
-            pos: p.clone(),
-            vel: v.clone(),
-            acc: a.clone(),
+            pos: *p,
+            vel: *v,
+            acc: *a,
         }
     }
 
     fn update(&mut self) {
-        self.vel += self.acc.clone();
-        self.pos += self.vel.clone();
+        self.vel += self.acc;
+        self.pos += self.vel;
     }
 
-    fn dist(&self) -> i64 {
-        i64::abs(self.pos.x) + i64::abs(self.pos.y) + i64::abs(self.pos.z)
+    // Position after t ticks.
+    fn at(&self, t: i64) -> VecN<D> {
+        self.pos + self.vel * t + self.acc * (t * (t + 1) / 2)
+    }
+
+    // Position, velocity and acceleration of this one relative to `other`.
+    fn relative(&self, other: &Particle<D>) -> Particle<D> {
+        Particle::new(&(self.pos - other.pos), &(self.vel - other.vel), &(self.acc - other.acc))
     }
 }
 
-fn s2i(s: &str) -> i64 {
-    i64::from_str_radix(s, 10).unwrap()
+impl<const D: usize> fmt::Display for VecN<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(|c| c.to_string()).collect();
+        write!(f, "<{}>", parts.join(","))
+    }
 }
 
-fn parse(row: &str) -> Particle {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r#"p=<(-?\d+),(-?\d+),(-?\d+)>, v=<(-?\d+),(-?\d+),(-?\d+)>, a=<(-?\d+),(-?\d+),(-?\d+)>"#).unwrap();
+impl<const D: usize> fmt::Display for Particle<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "p={}, v={}, a={}", self.pos, self.vel, self.acc)
     }
+}
 
-    let c = RE.captures(row).unwrap();
+// Everything before a '#' or "//".
+fn strip_comment(row: &str) -> &str {
+    let end = [row.find('#'), row.find("//")].iter().filter_map(|&i| i).min();
+    &row[..end.unwrap_or(row.len())]
+}
 
-    let p = Vec3::new(s2i(&c[1]), s2i(&c[2]), s2i(&c[3]));
-    let v = Vec3::new(s2i(&c[4]), s2i(&c[5]), s2i(&c[6]));
-    let a = Vec3::new(s2i(&c[7]), s2i(&c[8]), s2i(&c[9]));
+lazy_static! {
+    static ref FIELD: Regex = Regex::new(r"([pva])\s*=\s*<([^>]*)>").unwrap();
+}
 
-    Particle::new(&p, &v, &a)
+// The three fields in any order, with any whitespace around them.
+fn parse_particle<const D: usize>(row: &str) -> Result<Particle<D>, String> {
+    let row = strip_comment(row);
+    let mut fields: [Option<VecN<D>>; 3] = [None; 3];
+    for c in FIELD.captures_iter(row) {
+        let i = "pva".find(&c[1]).unwrap();
+        if fields[i].is_some() {
+            return Err(format!("{} given twice", &c[1]));
+        }
+        fields[i] = Some(c[2].parse()?);
+    }
+    let rest = FIELD.replace_all(row, "");
+    if let Some(c) = rest.chars().find(|&c| c != ',' && !c.is_whitespace()) {
+        return Err(format!("unexpected {:?}", c));
+    }
+    match fields {
+        [Some(p), Some(v), Some(a)] => Ok(Particle::new(&p, &v, &a)),
+        _ => {
+            let missing: String = "pva".chars().zip(fields.iter()).filter(|f| f.1.is_none()).map(|f| f.0).collect();
+            Err(format!("missing {}", missing))
+        }
+    }
 }
 
-fn run_1(particles: &mut Vec<Particle>) -> usize {
-    let mut dists: Vec<i64> = vec![];
-    for p in particles.iter() {
-        dists.push(p.dist());
+// One particle per line, skipping blank and comment lines.
+fn parse_swarm<const D: usize>(input: &str) -> Vec<Particle<D>> {
+    input.lines().enumerate()
+        .filter(|(_, row)| !strip_comment(row).trim().is_empty())
+        .map(|(i, row)| match parse_particle(row) {
+            Ok(p) => p,
+            Err(e) => panic!("line {}: {}", i + 1, e),
+        })
+        .collect()
+}
+
+// Number of coordinates in the swarm's first position.
+fn dimension(input: &str) -> usize {
+    let row = input.lines().map(strip_comment).find(|row| !row.trim().is_empty()).unwrap_or("");
+    match FIELD.captures_iter(row).find(|c| &c[1] == "p") {
+        Some(c) => c[2].split(',').count(),
+        None => panic!("not a particle: {:?}", row),
+    }
+}
+
+// SplitMix64, so swarms can be regenerated from their seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
     }
 
-    let iter = 10000000;
-    for i in 0..iter {
-        if 0 == i % 1000 {
-            println!("{}", iter - i);
+    fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + (self.next() % (hi - lo + 1) as u64) as i64
+    }
+
+    fn vec<const D: usize>(&mut self, r: i64) -> VecN<D> {
+        let mut v = VecN([0; D]);
+        for c in v.0.iter_mut() {
+            *c = self.range(-r, r);
         }
-        for(i,  p) in particles.iter_mut().enumerate() {
+        v
+    }
+}
+
+// A shuffled swarm of n particles in which exactly `pairs` pairs collide, each
+// on a tick of its own choosing, and nobody else ever meets. So run_2 should
+// leave n - 2·pairs.
+fn generate<const D: usize>(seed: u64, n: usize, pairs: usize) -> Vec<Particle<D>> {
+    assert!(2 * pairs <= n, "{} pairs don't fit in {} particles", pairs, n);
+    let mut rng = Rng(seed);
+    let mut swarm: Vec<Particle<D>> = Vec::with_capacity(n);
+    let clear = |swarm: &[Particle<D>], p: &Particle<D>| swarm.iter().all(|q| meet(p, q).is_none());
+
+    while swarm.len() < 2 * pairs {
+        // Run both particles backwards from the meeting point.
+        let t = rng.range(1, 50);
+        let x: VecN<D> = rng.vec(500);
+        let mut pair = Vec::new();
+        for _ in 0..2 {
+            let (v, a) = (rng.vec(20), rng.vec(3));
+            pair.push(Particle::new(&(x - v * t - a * (t * (t + 1) / 2)), &v, &a));
+        }
+        let distinct = pair[0].vel != pair[1].vel || pair[0].acc != pair[1].acc;
+        if distinct && meet(&pair[0], &pair[1]) == Some(t) && pair.iter().all(|p| clear(&swarm, p)) {
+            swarm.extend(pair);
+        }
+    }
+    while swarm.len() < n {
+        let p = Particle::new(&rng.vec(1000), &rng.vec(20), &rng.vec(3));
+        if clear(&swarm, &p) {
+            swarm.push(p);
+        }
+    }
+
+    for i in (1..swarm.len()).rev() {
+        let j = rng.range(0, i as i64) as usize;
+        swarm.swap(i, j);
+    }
+    swarm
+}
+
+// Sign of a coordinate once the particle is far enough out: set by the
+// acceleration, then the velocity, then the position.
+fn far_sign(p: i64, v: i64, a: i64) -> i64 {
+    if a != 0 {
+        a.signum()
+    }
+    else if v != 0 {
+        v.signum()
+    }
+    else {
+        p.signum()
+    }
+}
+
+impl<const D: usize> Particle<D> {
+    // After t ticks a particle is at p + v·t + a·t(t+1)/2. Once every axis has
+    // settled on its sign the distance is A·t(t+1)/2 + V·t + P, so particles
+    // order on (A, V, P) in the long run.
+    fn asymptotic(&self) -> (i64, i64, i64) {
+        (0..D).fold((0, 0, 0), |(sa, sv, sp), i| {
+            let (p, v, a) = (self.pos[i], self.vel[i], self.acc[i]);
+            let s = far_sign(p, v, a);
+            (sa + s * a, sv + s * v, sp + s * p)
+        })
+    }
+}
+
+fn run_1<const D: usize>(particles: &[Particle<D>]) -> usize {
+    (0..particles.len()).min_by_key(|&i| particles[i].asymptotic()).unwrap()
+}
+
+// Tick by tick, as a cross-check of run_1. Once every particle moves away from
+// the origin on every axis its distance grows by |v|·t + |a|·t(t+1)/2, so the
+// closest one stays closest when nobody is gaining on it. Returns it and the
+// number of ticks simulated.
+fn simulate_1<const D: usize>(particles: &[Particle<D>]) -> (usize, usize) {
+    let mut particles = particles.to_vec();
+    let origin = Particle::new(&VecN([0; D]), &VecN([0; D]), &VecN([0; D]));
+    let mut ticks = 0;
+    loop {
+        if particles.iter().all(|p| p.separating(&origin)) {
+            let closest = (0..particles.len()).min_by_key(|&i| particles[i].pos.manhattan()).unwrap();
+            let c = &particles[closest];
+            if particles.iter().all(|p| p.acc.manhattan() >= c.acc.manhattan() && p.vel.manhattan() >= c.vel.manhattan()) {
+                return (closest, ticks);
+            }
+        }
+        for p in particles.iter_mut() {
             p.update();
-            let d = p.dist();
-            if d < dists[i] {
-                dists[i] = d;
+        }
+        ticks += 1;
+    }
+}
+
+fn isqrt(n: i64) -> Option<i64> {
+    if n < 0 {
+        return None;
+    }
+    let mut r = (n as f64).sqrt() as i64;
+    while r * r > n {
+        r -= 1;
+    }
+    while (r + 1) * (r + 1) <= n {
+        r += 1;
+    }
+    if r * r == n { Some(r) } else { None }
+}
+
+// Ticks t >= 1 with a·t² + b·t + c = 0, or None if every tick is a solution.
+fn roots(a: i64, b: i64, c: i64) -> Option<Vec<i64>> {
+    let mut ts = Vec::new();
+    if a == 0 {
+        if b == 0 {
+            return if c == 0 { None } else { Some(ts) };
+        }
+        if c % b == 0 {
+            ts.push(-c / b);
+        }
+    }
+    else if let Some(d) = isqrt(b * b - 4 * a * c) {
+        for &n in &[-b - d, -b + d] {
+            if n % (2 * a) == 0 {
+                ts.push(n / (2 * a));
+            }
+        }
+    }
+    ts.retain(|&t| t >= 1);
+    ts.sort();
+    ts.dedup();
+    Some(ts)
+}
+
+// First tick at which two particles share a position. Per axis the difference
+// dp + dv·t + da·t(t+1)/2 is zero when da·t² + (da + 2dv)·t + 2dp = 0.
+fn meet<const D: usize>(p: &Particle<D>, q: &Particle<D>) -> Option<i64> {
+    let d = p.relative(q);
+    let mut common: Option<Vec<i64>> = None;
+    for i in 0..D {
+        let (dp, dv, da) = (d.pos[i], d.vel[i], d.acc[i]);
+        if let Some(ts) = roots(da, da + 2 * dv, 2 * dp) {
+            common = Some(match common {
+                None => ts,
+                Some(cs) => cs.into_iter().filter(|t| ts.contains(t)).collect(),
+            });
+        }
+    }
+    // Particles that coincide on every tick start out merged and meet on the first.
+    common.map_or(Some(1), |ts| ts.first().cloned())
+}
+
+#[derive(Debug, PartialEq)]
+struct Collision<const D: usize> {
+    tick: i64,
+    pos: VecN<D>,
+    ids: Vec<usize>,
+}
+
+// Collisions in time order: all pairs of live particles meeting on the same
+// tick go at once.
+fn timeline<const D: usize>(particles: &[Particle<D>]) -> Vec<Collision<D>> {
+    let mut events = Vec::new();
+    for i in 0..particles.len() {
+        for j in i + 1..particles.len() {
+            if let Some(t) = meet(&particles[i], &particles[j]) {
+                events.push((t, i, j));
             }
+        }
+    }
+    events.sort();
 
+    let mut alive = vec![true; particles.len()];
+    let mut collisions = Vec::new();
+    let mut k = 0;
+    while k < events.len() {
+        let t = events[k].0;
+        let mut hit: BTreeMap<VecN<D>, Vec<usize>> = BTreeMap::new();
+        while k < events.len() && events[k].0 == t {
+            let (_, i, j) = events[k];
+            if alive[i] && alive[j] {
+                let ids = hit.entry(particles[i].at(t)).or_default();
+                ids.extend_from_slice(&[i, j]);
+            }
+            k += 1;
         }
+        for (pos, mut ids) in hit {
+            ids.sort();
+            ids.dedup();
+            for &i in &ids {
+                alive[i] = false;
+            }
+            collisions.push(Collision { tick: t, pos, ids });
+        }
+    }
+    collisions
+}
+
+fn run_2<const D: usize>(particles: &[Particle<D>]) -> usize {
+    particles.len() - timeline(particles).iter().map(|c| c.ids.len()).sum::<usize>()
+}
+
+// Where each particle ends up in the long-run distance order, 0 being closest.
+fn ranks<const D: usize>(particles: &[Particle<D>]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..particles.len()).collect();
+    order.sort_by_key(|&i| particles[i].asymptotic());
+    let mut rank = vec![0; particles.len()];
+    for (r, &i) in order.iter().enumerate() {
+        rank[i] = r;
     }
+    rank
+}
 
-    let mut min = particles[0].dist();
-    let mut min_idx = 0;
-    for(i,  p) in particles.iter().enumerate() {
-        if p.dist() < min {
-            min = p.dist();
-            min_idx = i;
+fn report<const D: usize>(particles: &[Particle<D>]) -> String {
+    let collisions = timeline(particles);
+    let mut out = String::new();
+    let mut alive = vec![true; particles.len()];
+    for c in &collisions {
+        let ids: Vec<String> = c.ids.iter().map(|i| i.to_string()).collect();
+        out.push_str(&format!("tick {} at {}: {}\n", c.tick, c.pos, ids.join(" ")));
+        for &i in &c.ids {
+            alive[i] = false;
         }
     }
-    min_idx
+    let rank = ranks(particles);
+    let survivors: Vec<usize> = (0..particles.len()).filter(|&i| alive[i]).collect();
+    out.push_str(&format!("{} survivors\n", survivors.len()));
+    for i in survivors {
+        out.push_str(&format!("{} rank {}\n", i, rank[i]));
+    }
+    out
 }
 
-fn run_2(particles: &mut Vec<Particle>) -> usize {
-    let iter = 100000;
-    for idx in 0..iter {
-        if 0 == idx % 1000 {
-            println!("{} - {}", iter - idx, particles.len());
+// One row per particle: its asymptotic rank, and when and where it collided
+// unless it survived.
+fn report_csv<const D: usize>(particles: &[Particle<D>]) -> String {
+    let axes: Vec<String> = (0..D).map(|i| match "xyzw".chars().nth(i) {
+        Some(c) => c.to_string(),
+        None => format!("x{}", i),
+    }).collect();
+    let mut rows = vec![None; particles.len()];
+    for c in timeline(particles) {
+        for &i in &c.ids {
+            rows[i] = Some((c.tick, c.pos));
         }
+    }
 
-        for p in particles.iter_mut() {
+    let mut out = format!("particle,rank,tick,{}\n", axes.join(","));
+    for (i, rank) in ranks(particles).into_iter().enumerate() {
+        let (tick, pos) = match rows[i] {
+            Some((t, p)) => (t.to_string(), p.0.iter().map(|c| c.to_string()).collect()),
+            None => (String::new(), vec![String::new(); D]),
+        };
+        out.push_str(&format!("{},{},{},{}\n", i, rank, tick, pos.join(",")));
+    }
+    out
+}
+
+impl<const D: usize> Particle<D> {
+    // Whether the distance to `other` can only grow from now on: on every axis
+    // the position, velocity and acceleration differences never pull against
+    // each other.
+    fn separating(&self, other: &Particle<D>) -> bool {
+        let d = self.relative(other);
+        (0..D).all(|i| {
+            let (p, v, a) = (d.pos[i], d.vel[i], d.acc[i]);
+            p * v >= 0 && p * a >= 0 && v * a >= 0
+        })
+    }
+}
+
+fn stable<const D: usize>(particles: &[Particle<D>]) -> bool {
+    (0..particles.len()).all(|i| {
+        (i + 1..particles.len()).all(|j| particles[i].separating(&particles[j]))
+    })
+}
+
+// Tick by tick, as a cross-check of run_2, until no pair can meet any more.
+// Returns the survivors and the number of ticks simulated.
+fn simulate_2<const D: usize>(particles: &mut Vec<Particle<D>>) -> (usize, usize) {
+    let mut ticks = 0;
+    while !stable(particles) {
+        ticks += 1;
+        let mut buckets: HashMap<VecN<D>, Vec<usize>> = HashMap::new();
+        for (i, p) in particles.iter_mut().enumerate() {
             p.update();
+            buckets.entry(p.pos).or_default().push(i);
         }
 
-        let mut i = 0;
-        while i < particles.len() {
-            let mut d = false;
-            for j in (i+1..particles.len()).rev() {
-                if particles[i].pos == particles[j].pos {
-                    println!("remove {}", j);
-                   particles.remove(j);
-                   d = true;
-                }
+        let mut hit = vec![false; particles.len()];
+        for ids in buckets.values().filter(|ids| ids.len() > 1) {
+            for &i in ids {
+                hit[i] = true;
             }
+        }
+        let mut i = 0;
+        particles.retain(|_| {
+            i += 1;
+            !hit[i - 1]
+        });
+    }
 
-            if d {
-                particles.remove(i);
-            }
-            else {
-                i += 1;
-            }
+    (particles.len(), ticks)
+}
+
+fn solve<const D: usize>(input: &str, mode: &str) {
+    let mut particles: Vec<Particle<D>> = parse_swarm(input);
+    match mode {
+        "simulate" => {
+            let (closest, ticks) = simulate_1(&particles);
+            println!("aoc20-1: {} (settled after {} ticks)", closest, ticks);
+            let (left, ticks) = simulate_2(&mut particles);
+            println!("aoc20-2: {} (stable after {} ticks)", left, ticks);
+        },
+        "timeline" => print!("{}", report(&particles)),
+        "csv" => print!("{}", report_csv(&particles)),
+        _ => {
+            println!("aoc20-1: {}", run_1(&particles));
+            println!("aoc20-2: {}", run_2(&particles));
         }
     }
+}
 
-    particles.len()
-    //506 too high
+fn emit<const D: usize>(seed: u64, n: usize, pairs: usize) {
+    println!("# seed {}: {} particles, {} colliding pairs, {} survivors", seed, n, pairs, n - 2 * pairs);
+    for p in generate::<D>(seed, n, pairs) {
+        println!("{}", p);
+    }
 }
 
 pub fn run() {
-    let input = fs::read_to_string("day20.txt").unwrap();
-    let mut particles : Vec<Particle> = input.lines().map(parse).collect();
-    println!("aoc20-1: {}", run_1(&mut particles));
-    let mut particles : Vec<Particle> = input.lines().map(parse).collect();
-    println!("aoc20-2: {}", run_2(&mut particles));
+    let mut args: Vec<String> = env::args().skip(2).collect();
+    if args.first().map(|s| s.as_str()) == Some("generate") {
+        let num = |i: usize, default: usize| args.get(i).map_or(default, |s| s.parse().unwrap());
+        let (seed, n, pairs) = (num(1, 1) as u64, num(2, 1000), num(3, 100));
+        match num(4, 3) {
+            2 => emit::<2>(seed, n, pairs),
+            3 => emit::<3>(seed, n, pairs),
+            4 => emit::<4>(seed, n, pairs),
+            d => panic!("{}-dimensional swarms aren't supported", d),
+        }
+        return;
+    }
+    let modes = ["simulate", "timeline", "csv"];
+    let mode = if args.first().is_some_and(|s| modes.contains(&s.as_str())) { args.remove(0) } else { String::new() };
+    let name = args.first().map_or("day20.txt", |s| s.as_str());
+    let input = fs::read_to_string(name).unwrap();
+    match dimension(&input) {
+        1 => solve::<1>(&input, &mode),
+        2 => solve::<2>(&input, &mode),
+        3 => solve::<3>(&input, &mode),
+        4 => solve::<4>(&input, &mode),
+        d => panic!("{}-dimensional swarms aren't supported", d),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    type Vec3 = VecN<3>;
+
+    impl Vec3 {
+        fn new(x: i64, y: i64, z: i64) -> Self {
+            VecN([x, y, z])
+        }
+    }
+
+    fn parse<const D: usize>(row: &str) -> Particle<D> {
+        match parse_particle(row) {
+            Ok(p) => p,
+            Err(e) => panic!("{}: {:?}", e, row),
+        }
+    }
+
     #[test]
     fn aoc20_add() {
         assert_eq!(Vec3::new(1,2,3), Vec3::new(0,0,0) + Vec3::new(1,2,3));
     }
 
+    #[test]
+    fn aoc20_vector_math() {
+        let a = Vec3::new(1, -2, 3);
+        let b = Vec3::new(4, 0, -1);
+        assert_eq!(Vec3::new(-3, -2, 4), a - b);
+        assert_eq!(Vec3::new(-1, 2, -3), -a);
+        assert_eq!(Vec3::new(3, -6, 9), a * 3);
+        assert_eq!(6, a.manhattan());
+        assert_eq!(5.0, VecN([3, 4]).euclidean());
+        assert_eq!(Ok(a), "<1,-2,3>".parse());
+        assert_eq!(Ok(VecN([7, 0, 0, -1])), " 7, 0,0 ,-1".parse());
+        assert!("<1,2>".parse::<Vec3>().is_err());
+        assert!("<1,2,3,4>".parse::<Vec3>().is_err());
+        assert!("<1,x,3>".parse::<Vec3>().is_err());
+    }
+
+    #[test]
+    fn aoc20_dimensions() {
+        let flat = "p=<-6,0>, v=<3,0>, a=<0,0>\np=<-4,0>, v=<2,0>, a=<0,0>\np=<-2,0>, v=<1,0>, a=<0,0>\np=<3,0>, v=<-1,0>, a=<0,0>";
+        assert_eq!(2, dimension(flat));
+        let mut particles: Vec<Particle<2>> = flat.lines().map(parse).collect();
+        assert_eq!(1, run_2(&particles));
+        assert_eq!((1, 2), simulate_2(&mut particles));
+
+        let wide = "p=<0,0,0,5>, v=<0,0,0,0>, a=<0,0,0,-1>\np=<0,0,0,4>, v=<0,0,0,0>, a=<0,0,0,0>\np=<1,0,0,0>, v=<0,0,0,0>, a=<0,0,0,0>";
+        assert_eq!(4, dimension(wide));
+        let particles: Vec<Particle<4>> = wide.lines().map(parse).collect();
+        assert_eq!(Some(1), meet(&particles[0], &particles[1]));
+        assert_eq!(1, run_2(&particles));
+        assert_eq!(2, run_1(&particles));
+        assert_eq!(2, simulate_1(&particles).0);
+    }
+
     #[test]
     fn aoc20_parse() {
         assert_eq!(Particle::new(
@@ -172,11 +630,152 @@ mod tests {
                 ), parse("p=<-10088,3682,-5210>, v=<52,32,-38>, a=<14,-8,11>"));
     }
 
+    #[test]
+    fn aoc20_parse_tolerant() {
+        let p: Particle = parse("p=<1,2,3>, v=<4,5,6>, a=<7,8,9>");
+        assert_eq!(p, parse("  a = < 7, 8, 9 >  p=< 1,2 ,3>,v=<4,5,6> "));
+        assert_eq!(p, parse("v=<4,5,6>, a=<7,8,9>, p=<1,2,3> # from the example"));
+        assert_eq!("p=<1,2,3>, v=<4,5,6>, a=<7,8,9>", p.to_string());
+
+        assert_eq!(Err("missing a".to_string()), parse_particle::<3>("p=<1,2,3>, v=<4,5,6>"));
+        assert_eq!(Err("p given twice".to_string()), parse_particle::<3>("p=<1,2,3>, v=<4,5,6>, a=<7,8,9>, p=<0,0,0>"));
+        assert_eq!(Err("unexpected 'q'".to_string()), parse_particle::<3>("p=<1,2,3>, v=<4,5,6>, a=<7,8,9>, q"));
+        assert!(parse_particle::<2>("p=<1,2,3>, v=<4,5,6>, a=<7,8,9>").is_err());
+
+        let input = "// two particles\n\np=<1,2>, v=<0,0>, a=<0,0>\n  # nothing here\na=<1,1>, v=<0,0>, p=<3,4> // last\n";
+        assert_eq!(2, dimension(input));
+        let swarm: Vec<Particle<2>> = parse_swarm(input);
+        assert_eq!(vec![VecN([1, 2]), VecN([3, 4])], swarm.iter().map(|p| p.pos).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "line 2: missing v")]
+    fn aoc20_parse_line_numbers() {
+        parse_swarm::<3>("p=<1,2,3>, v=<4,5,6>, a=<7,8,9>\np=<1,2,3>, a=<7,8,9>");
+    }
+
+    #[test]
+    fn aoc20_generate() {
+        let swarm: Vec<Particle> = generate(7, 400, 60);
+        assert_eq!(400, swarm.len());
+        assert_eq!(280, run_2(&swarm));
+        assert_eq!(run_1(&swarm), simulate_1(&swarm).0);
+        assert_eq!(280, simulate_2(&mut swarm.clone()).0);
+
+        // the text form reads back the same
+        let text: Vec<String> = swarm.iter().map(|p| p.to_string()).collect();
+        assert_eq!(swarm, parse_swarm::<3>(&text.join("\n")));
+        assert_eq!(swarm, generate::<3>(7, 400, 60));
+        assert!(swarm != generate::<3>(8, 400, 60));
+
+        let flat: Vec<Particle<2>> = generate(1, 200, 100);
+        assert_eq!(0, run_2(&flat));
+        let wide: Vec<Particle<4>> = generate(1, 100, 0);
+        assert_eq!(100, run_2(&wide));
+    }
+
     #[test]
     fn aoc20_2() {
         let input = "p=<-6,0,0>, v=<3,0,0>, a=<0,0,0>\np=<-4,0,0>, v=<2,0,0>, a=<0,0,0>\np=<-2,0,0>, v=<1,0,0>, a=<0,0,0>\np=<3,0,0>, v=<-1,0,0>, a=<0,0,0>";
 
         let mut particles : Vec<Particle> = input.lines().map(parse).collect();
-        assert_eq!(1, run_2(&mut particles));
+        assert_eq!(1, run_2(&particles));
+        assert_eq!((1, 2), simulate_2(&mut particles));
+    }
+
+    #[test]
+    fn aoc20_timeline() {
+        let input = "p=<-6,0,0>, v=<3,0,0>, a=<0,0,0>\np=<-4,0,0>, v=<2,0,0>, a=<0,0,0>\np=<-2,0,0>, v=<1,0,0>, a=<0,0,0>\np=<3,0,0>, v=<-1,0,0>, a=<0,0,0>";
+        let particles: Vec<Particle> = parse_swarm(input);
+        assert_eq!(vec![Collision { tick: 2, pos: Vec3::new(0, 0, 0), ids: vec![0, 1, 2] }], timeline(&particles));
+        assert_eq!(vec![3, 2, 1, 0], ranks(&particles));
+        assert_eq!("tick 2 at <0,0,0>: 0 1 2\n1 survivors\n3 rank 0\n", report(&particles));
+        assert_eq!("particle,rank,tick,x,y,z\n0,3,2,0,0,0\n1,2,2,0,0,0\n2,1,2,0,0,0\n3,0,,,,\n", report_csv(&particles));
+
+        // two separate collisions on the same tick
+        let input = "p=<0,0>, v=<1,0>, a=<0,0>\np=<2,0>, v=<-1,0>, a=<0,0>\np=<0,5>, v=<1,0>, a=<0,0>\np=<2,5>, v=<-1,0>, a=<0,0>";
+        let particles: Vec<Particle<2>> = parse_swarm(input);
+        let collisions = timeline(&particles);
+        assert_eq!(2, collisions.len());
+        assert_eq!((1, VecN([1, 0]), vec![0, 1]), (collisions[0].tick, collisions[0].pos, collisions[0].ids.clone()));
+        assert_eq!((1, VecN([1, 5]), vec![2, 3]), (collisions[1].tick, collisions[1].pos, collisions[1].ids.clone()));
+        assert!(report_csv(&particles).starts_with("particle,rank,tick,x,y\n"));
+
+        // the analytic position matches stepping
+        let mut p: Particle = parse("p=<3,-1,2>, v=<0,4,-2>, a=<-1,1,2>");
+        let start = p.clone();
+        for t in 1..20 {
+            p.update();
+            assert_eq!(p.pos, start.at(t));
+        }
+    }
+
+    #[test]
+    fn aoc20_1() {
+        let input = "p=<3,0,0>, v=<2,0,0>, a=<-1,0,0>\np=<4,0,0>, v=<0,0,0>, a=<-2,0,0>";
+        let particles : Vec<Particle> = input.lines().map(parse).collect();
+        assert_eq!(0, run_1(&particles));
+
+        // Equal acceleration: the one drifting back towards the origin wins
+        // even though it starts further out.
+        let input = "p=<1,0,0>, v=<1,0,0>, a=<1,1,0>\np=<-9,0,0>, v=<2,0,0>, a=<1,-1,0>";
+        let particles : Vec<Particle> = input.lines().map(parse).collect();
+        assert_eq!((2, 1, 1), particles[0].asymptotic());
+        assert_eq!((2, 2, -9), particles[1].asymptotic());
+        assert_eq!(0, run_1(&particles));
+        assert_eq!(0, simulate_1(&particles).0);
+    }
+
+    #[test]
+    fn aoc20_roots() {
+        assert_eq!(None, roots(0, 0, 0));
+        assert_eq!(Some(vec![]), roots(0, 0, 1));
+        assert_eq!(Some(vec![3]), roots(0, 2, -6));
+        assert_eq!(Some(vec![2, 5]), roots(1, -7, 10));
+        assert_eq!(Some(vec![]), roots(1, 0, 2));
+        assert_eq!(Some(vec![3]), roots(2, -3, -9));
+    }
+
+    #[test]
+    fn aoc20_meet() {
+        // Accelerating particles catching up with each other on tick 4.
+        let p: Particle = parse("p=<0,0,0>, v=<0,1,0>, a=<2,0,0>");
+        let q = parse("p=<20,4,0>, v=<0,0,0>, a=<0,0,0>");
+        assert_eq!(Some(4), meet(&p, &q));
+        assert_eq!(Some(4), meet(&q, &p));
+
+        let mut particles = vec![p.clone(), q.clone()];
+        assert_eq!(0, run_2(&particles));
+        assert_eq!((0, 4), simulate_2(&mut particles));
+
+        assert_eq!(None, meet(&p, &parse("p=<20,4,1>, v=<0,0,0>, a=<0,0,0>")));
+    }
+
+    #[test]
+    fn aoc20_later_collision_needs_survivors() {
+        // 0 and 1 meet at tick 1, so 0 is gone when 2 would reach it at tick 2.
+        let input = "p=<0,0,0>, v=<0,0,0>, a=<0,0,0>\np=<2,0,0>, v=<-2,0,0>, a=<0,0,0>\np=<-4,0,0>, v=<2,0,0>, a=<0,0,0>";
+        let mut particles : Vec<Particle> = input.lines().map(parse).collect();
+        assert_eq!(1, run_2(&particles));
+        assert_eq!(1, simulate_2(&mut particles).0);
+    }
+
+    #[test]
+    fn aoc20_stable() {
+        let p: Particle = parse("p=<0,0,0>, v=<1,0,0>, a=<0,0,0>");
+        assert!(p.separating(&parse("p=<-1,0,0>, v=<0,0,0>, a=<0,0,0>")));
+        assert!(p.separating(&parse("p=<0,5,0>, v=<1,0,0>, a=<0,1,0>")));
+        // behind but accelerating: it catches up eventually
+        assert!(!p.separating(&parse("p=<-1,0,0>, v=<0,0,0>, a=<1,0,0>")));
+        // ahead but slowing down
+        assert!(!p.separating(&parse("p=<5,0,0>, v=<1,0,0>, a=<-1,0,0>")));
+
+        let mut particles = vec![p.clone(), parse("p=<-11,0,0>, v=<0,0,0>, a=<1,0,0>")];
+        assert!(!stable(&particles));
+        let (left, ticks) = simulate_2(&mut particles);
+        assert_eq!(2, left);
+        assert!(stable(&particles));
+        assert!(particles[1].vel[0] >= particles[0].vel[0] && particles[1].pos[0] > particles[0].pos[0]);
+        assert!(ticks > 0);
     }
 }
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs;
 // use std::collections;
 
@@ -11,31 +14,93 @@ struct Rule {
 }
 
 impl Rule {
+    // A source that isn't square is kept as it is, for validate to report.
     fn new(source: Map, dest: Map) -> Self {
-        let size = source.len();
-        let mut sources = Vec::new();
-        let mut r = source.clone();
+        Rule {
+            size: source.len(),
+            sources: if square(&source) { orientations(&source) } else { vec![source] },
+            dest,
+        }
+    }
+}
 
-        for _ in 0..3 {
+fn square(m: &Map) -> bool {
+    m.iter().all(|row| row.len() == m.len())
+}
+
+// The distinct images of a pattern under the eight symmetries of a square,
+// starting with the pattern itself.
+fn orientations(pattern: &Map) -> Vec<Map> {
+    let mut all = Vec::new();
+    for start in [pattern.clone(), flip(pattern)] {
+        let mut r = start;
+        for _ in 0..4 {
+            if !all.contains(&r) {
+                all.push(r.clone());
+            }
             r = rotate(&r);
-            sources.push(r.clone());
         }
+    }
+    all
+}
 
-        r = flip(&source);
-        sources.push(r.clone());
-        for _ in 0..3 {
-            r = rotate(&r);
-            sources.push(r.clone());
+// Same for all orientations of a pattern.
+fn canonical(pattern: &Map) -> Map {
+    orientations(pattern).into_iter().min().unwrap()
+}
+
+#[derive(Debug, PartialEq)]
+enum RuleProblem {
+    Missing(Map),
+    Conflict(usize, usize),
+    Duplicate(usize, usize),
+    BadSize(usize),
+    Unsplittable(usize),
+}
+
+impl fmt::Display for RuleProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuleProblem::Missing(ref m) => write!(f, "no rule for {}", format_map(m)),
+            RuleProblem::Conflict(a, b) => write!(f, "rules {} and {} match the same pattern with different outputs", a + 1, b + 1),
+            RuleProblem::Duplicate(a, b) => write!(f, "rule {} repeats rule {}", b + 1, a + 1),
+            RuleProblem::BadSize(a) => write!(f, "rule {} doesn't turn a square into one a size larger", a + 1),
+            RuleProblem::Unsplittable(n) => write!(f, "a {0}x{0} map can't be split into blocks of any rule size", n),
         }
+    }
+}
 
-        sources.push(source);
+// Everything wrong with a rule book, including patterns of the given sizes no
+// rule covers. Only blocks up to DENSE_BLOCK are checked for those, beyond
+// that there are too many patterns.
+fn validate(rules: &[Rule], sizes: &[usize]) -> Vec<RuleProblem> {
+    let mut problems = Vec::new();
+    let mut seen: HashMap<Map, usize> = HashMap::new();
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.size == 0 || rule.size > MAX_BLOCK || !square(&rule.sources[0]) || !square(&rule.dest) || rule.dest.len() != rule.size + 1 {
+            problems.push(RuleProblem::BadSize(i));
+            continue;
+        }
+        let key = canonical(&rule.sources[0]);
+        match seen.get(&key) {
+            Some(&j) if rules[j].dest == rule.dest => problems.push(RuleProblem::Duplicate(j, i)),
+            Some(&j) => problems.push(RuleProblem::Conflict(j, i)),
+            None => {
+                seen.insert(key, i);
+            }
+        }
+    }
 
-        Rule {
-            sources: sources,
-            dest: dest,
-            size: size,
+    for &size in sizes.iter().filter(|&&k| k <= DENSE_BLOCK) {
+        for bits in 0..1 << (size * size) {
+            let pattern = decode(bits, size);
+            let key = canonical(&pattern);
+            if key == pattern && !seen.contains_key(&key) {
+                problems.push(RuleProblem::Missing(pattern));
+            }
         }
     }
+    problems
 }
 
 fn start_map() -> Map {
@@ -63,165 +128,493 @@ fn parse_map(s: &str) -> Map {
 
 fn parse_rule(s: &str) -> Rule {
     let row: Vec<&str> = s.split(" => ").collect();
-    let src = parse_map(&row[0]);
-    let dst = parse_map(&row[1]);
+    let src = parse_map(row[0]);
+    let dst = parse_map(row[1]);
     Rule::new(src, dst)
 }
 
-fn new_map(size: usize) -> Map {
-    let mut m = Map::new();
+// A square pattern packed row by row into an integer, the top left cell in the
+// lowest bit: 4 bits for 2x2, 9 for 3x3 and 16 for 4x4.
+type Pattern = u128;
+
+// Largest block a rule may match: its output has to fit in a Pattern too.
+const MAX_BLOCK: usize = 10;
 
-    for _ in 0..size {
-        let row = vec![false; size];
-        m.push(row);
+// Blocks up to this size are looked up in a table with a slot for every
+// pattern, and checked for missing rules.
+const DENSE_BLOCK: usize = 4;
+
+const DEFAULT_SPLIT: [usize; 2] = [2, 3];
+
+fn encode(pattern: &Map) -> Pattern {
+    let size = pattern.len();
+    let mut bits = 0;
+    for (r, row) in pattern.iter().enumerate() {
+        for (c, &on) in row.iter().enumerate() {
+            if on {
+                bits |= 1 << (r * size + c);
+            }
+        }
     }
+    bits
+}
 
-    m
+fn decode(bits: Pattern, size: usize) -> Map {
+    (0..size).map(|r| (0..size).map(|c| bits >> (r * size + c) & 1 == 1).collect()).collect()
 }
 
-fn expand(map: Map, rules: &[Rule]) -> Map {
-    let step = if map.len() % 2 == 0 {
-        2
-    }
-    else {
-        3
-    };
+// Output pattern for every encoded block, and the block sizes to split a map
+// into in order of preference.
+struct Book {
+    split: Vec<usize>,
+    dense: Vec<Vec<Option<Pattern>>>,
+    sparse: HashMap<(usize, Pattern), Pattern>,
+}
 
-    let new_step = step + 1;
-    let mut new_map = new_map(map.len() / step * new_step);
-
-    let mut r = 0;
-    let mut new_r = 0;
-    while r < map.len() {
-        let mut c = 0;
-        let mut new_c = 0;
-        while c < map.len() {
-            let mut found = false;
-            for rule in rules {
-                if rule.size != step {
-                    continue;
-                }
-                for s in &rule.sources {
-                    if is_match(s, &map, r, c) {
-                        // println!("Match");
-                        // print_map(s);
-                        set_pattern(&rule.dest, &mut new_map, new_r, new_c);
-                        found = true;
-                        break;
-                    }
+impl Book {
+    fn new(rules: &[Rule], split: &[usize]) -> Self {
+        let mut book = Book {
+            split: split.to_vec(),
+            dense: (0..DENSE_BLOCK + 1).map(|size| vec![None; 1 << (size * size)]).collect(),
+            sparse: HashMap::new(),
+        };
+        for rule in rules {
+            let out = encode(&rule.dest);
+            for s in &rule.sources {
+                if rule.size <= DENSE_BLOCK {
+                    book.dense[rule.size][encode(s) as usize] = Some(out);
                 }
-                if found {
-                    break;
+                else {
+                    book.sparse.insert((rule.size, encode(s)), out);
                 }
             }
-            if !found {
-                print_map(&map);
-                println!("row: {} col: {} step: {}", r, c, step);
-                panic!("No rule!!");
-            }
+        }
+        book
+    }
 
-            c += step;
-            new_c += new_step;
+    fn get(&self, size: usize, block: Pattern) -> Result<Pattern, RuleProblem> {
+        let out = if size <= DENSE_BLOCK {
+            self.dense[size][block as usize]
         }
-        r += step;
-        new_r += new_step;
+        else {
+            self.sparse.get(&(size, block)).cloned()
+        };
+        out.ok_or_else(|| RuleProblem::Missing(decode(block, size)))
+    }
+
+    // Block size for a map of the given size.
+    fn step(&self, size: usize) -> Result<usize, RuleProblem> {
+        self.split.iter().cloned().find(|&k| size.is_multiple_of(k)).ok_or(RuleProblem::Unsplittable(size))
     }
-    println!("new_map size: {}", new_map.len());
-    new_map
 }
 
-pub fn run() {
-    let input = fs::read_to_string("day21.txt").unwrap();
-    let rules : Vec<Rule> = input.lines().map(parse_rule).collect();
-    println!("aoc21-1: {}", run_1(&rules, 5));
-    println!("aoc21-2: {}", run_1(&rules, 18));
+// A square of cells packed 64 to a word, each row starting on a new word.
+#[derive(Debug, Clone, PartialEq)]
+struct BitGrid {
+    size: usize,
+    words: usize,
+    bits: Vec<u64>,
 }
 
-fn run_1(rules: &[Rule], iters: usize) -> usize {
-    let mut map = start_map();
-    println!("start map");
-    print_map(&map);
-    for _ in 0..iters {
-        map = expand(map, rules);
-        // println!("result");
-        // print_map(&map);
-    }
-    let mut cnt = 0;
-    for row in map.iter() {
-        for c in row.iter() {
-            if *c {
-                cnt+=1;
+impl BitGrid {
+    fn new(size: usize) -> Self {
+        let mut g = BitGrid { size: 0, words: 0, bits: Vec::new() };
+        g.reset(size);
+        g
+    }
+
+    // Clears the grid and resizes it, keeping the allocation.
+    fn reset(&mut self, size: usize) {
+        self.size = size;
+        self.words = size.div_ceil(64);
+        self.bits.clear();
+        self.bits.resize(size * self.words, 0);
+    }
+
+    fn from_map(m: &Map) -> Self {
+        let mut g = BitGrid::new(m.len());
+        for (r, row) in m.iter().enumerate() {
+            for (c, &on) in row.iter().enumerate() {
+                if on {
+                    g.set(r, c);
+                }
             }
         }
+        g
     }
-    cnt
-}
 
-fn is_match(pattern: &Map, map: &Map, check_row: usize, check_col: usize) -> bool {
-    for (r, row) in pattern.iter().enumerate() {
-        for (c, col) in row.iter().enumerate() {
-            if *col != map[r+check_row][c+check_col] {
-                return false;
+    fn get(&self, r: usize, c: usize) -> bool {
+        self.bits[r * self.words + c / 64] >> (c % 64) & 1 == 1
+    }
+
+    fn set(&mut self, r: usize, c: usize) {
+        self.bits[r * self.words + c / 64] |= 1 << (c % 64);
+    }
+
+    fn count(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    // The k x k block at (r, c), encoded.
+    fn block(&self, r: usize, c: usize, k: usize) -> Pattern {
+        let mut bits = 0;
+        for i in 0..k {
+            for j in 0..k {
+                if self.get(r + i, c + j) {
+                    bits |= 1 << (i * k + j);
+                }
+            }
+        }
+        bits
+    }
+
+    fn put(&mut self, r: usize, c: usize, k: usize, pattern: Pattern) {
+        for i in 0..k {
+            for j in 0..k {
+                if pattern >> (i * k + j) & 1 == 1 {
+                    self.set(r + i, c + j);
+                }
             }
         }
     }
-    true
 }
 
-fn set_pattern(pattern: &Map, map: &mut Map, check_row: usize, check_col: usize) {
-    for (r,row) in pattern.iter().enumerate() {
-        for (c, col) in row.iter().enumerate() {
-            if *col {
-                map[r+check_row][c+check_col] = true;
+fn expand(map: &BitGrid, book: &Book, out: &mut BitGrid) -> Result<(), RuleProblem> {
+    let step = book.step(map.size)?;
+    let blocks = map.size / step;
+    out.reset(blocks * (step + 1));
+
+    for r in 0..blocks {
+        for c in 0..blocks {
+            let pattern = book.get(step, map.block(r * step, c * step, step))?;
+            out.put(r * (step + 1), c * (step + 1), step + 1, pattern);
+        }
+    }
+    Ok(())
+}
+
+// Unsigned integer of any size, as base 10^9 digits with the lowest first.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Big(Vec<u64>);
+
+const BIG_BASE: u64 = 1_000_000_000;
+
+impl Big {
+    fn from(mut n: u64) -> Self {
+        let mut digits = Vec::new();
+        while n > 0 {
+            digits.push(n % BIG_BASE);
+            n /= BIG_BASE;
+        }
+        Big(digits)
+    }
+
+    fn add(&mut self, other: &Big) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        let mut carry = 0;
+        for i in 0..self.0.len() {
+            let sum = self.0[i] + other.0.get(i).cloned().unwrap_or(0) + carry;
+            self.0[i] = sum % BIG_BASE;
+            carry = sum / BIG_BASE;
+        }
+        if carry > 0 {
+            self.0.push(carry);
+        }
+    }
+
+    fn times(&self, m: u64) -> Big {
+        let mut out = Vec::with_capacity(self.0.len() + 1);
+        let mut carry = 0;
+        for &d in &self.0 {
+            let p = d as u128 * m as u128 + carry;
+            out.push((p % BIG_BASE as u128) as u64);
+            carry = p / BIG_BASE as u128;
+        }
+        while carry > 0 {
+            out.push((carry % BIG_BASE as u128) as u64);
+            carry /= BIG_BASE as u128;
+        }
+        while out.last() == Some(&0) {
+            out.pop();
+        }
+        Big(out)
+    }
+}
+
+impl fmt::Display for Big {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.split_last() {
+            None => write!(f, "0"),
+            Some((top, rest)) => {
+                write!(f, "{}", top)?;
+                for d in rest.iter().rev() {
+                    write!(f, "{:09}", d)?;
+                }
+                Ok(())
             }
         }
     }
 }
 
-fn rotate(pattern: &Map) -> Map {
-    if pattern.len() == 2 {
-        rotate2(pattern)
+fn enhance(start: BitGrid, book: &Book, iters: usize) -> Result<BitGrid, RuleProblem> {
+    let mut map = start;
+    let mut next = BitGrid::new(0);
+    for _ in 0..iters {
+        expand(&map, book, &mut next)?;
+        std::mem::swap(&mut map, &mut next);
     }
-    else {
-        rotate3(pattern)
+    Ok(map)
+}
+
+// The 3x3 block `block` expanded `iters` times on its own.
+fn grow(book: &Book, block: Pattern, iters: usize) -> Result<BitGrid, RuleProblem> {
+    let mut map = BitGrid::new(3);
+    map.put(0, 0, 3, block);
+    enhance(map, book, iters)
+}
+
+// Lit pixels after `iters` iterations without building the map. Once a map
+// splits into 3x3 blocks, three iterations later each has become a 9x9 square
+// of 3x3 blocks regardless of its neighbours, so it's enough to count how many
+// there are of each. This relies on the default split.
+fn count_lit(start: &Map, rules: &[Rule], iters: usize) -> Result<Big, RuleProblem> {
+    let book = Book::new(rules, &DEFAULT_SPLIT);
+    let mut map = BitGrid::from_map(start);
+    let mut next = BitGrid::new(0);
+    let mut left = iters;
+    while left > 0 && map.size.is_multiple_of(2) {
+        expand(&map, &book, &mut next)?;
+        std::mem::swap(&mut map, &mut next);
+        left -= 1;
+    }
+    if left == 0 {
+        return Ok(Big::from(map.count() as u64));
+    }
+    if !map.size.is_multiple_of(3) {
+        return Err(RuleProblem::Unsplittable(map.size));
     }
+
+    let mut counts: HashMap<Pattern, Big> = HashMap::new();
+    for r in (0..map.size).step_by(3) {
+        for c in (0..map.size).step_by(3) {
+            counts.entry(map.block(r, c, 3)).or_default().add(&Big::from(1));
+        }
+    }
+
+    let mut children: HashMap<Pattern, Vec<Pattern>> = HashMap::new();
+    while left >= 3 {
+        let mut next_counts: HashMap<Pattern, Big> = HashMap::new();
+        for (block, n) in &counts {
+            if !children.contains_key(block) {
+                let g = grow(&book, *block, 3)?;
+                children.insert(*block, (0..9).map(|i| g.block(i / 3 * 3, i % 3 * 3, 3)).collect());
+            }
+            for kid in &children[block] {
+                next_counts.entry(*kid).or_default().add(n);
+            }
+        }
+        counts = next_counts;
+        left -= 3;
+    }
+
+    let mut total = Big::default();
+    for (block, n) in &counts {
+        total.add(&n.times(grow(&book, *block, left)?.count() as u64));
+    }
+    Ok(total)
 }
 
-fn rotate2(pattern: &Map) -> Map {
-    let mut r = pattern.clone();
+const LIT: u8 = 0;
+const DARK: u8 = 255;
+const LINE: u8 = 150;
+const GAP: u8 = 210;
+
+// The map as rows of grey levels, each cell a scale x scale square. With a
+// block size, one pixel lines are drawn around the blocks.
+fn raster(map: &BitGrid, scale: usize, block: Option<usize>) -> Vec<Vec<u8>> {
+    let k = block.unwrap_or(map.size.max(1));
+    let lines = block.map_or(0, |k| map.size / k + 1);
+    let side = map.size * scale + lines;
+    // image coordinate -> cell, or None on a line
+    let cell = |x: usize| -> Option<usize> {
+        if block.is_none() {
+            return Some(x / scale);
+        }
+        let span = k * scale + 1;
+        let (b, o) = (x / span, x % span);
+        if o == 0 { None } else { Some(b * k + (o - 1) / scale) }
+    };
 
-    r[0][0] = pattern[0][1];
-    r[0][1] = pattern[1][1];
-    r[1][0] = pattern[0][0];
-    r[1][1] = pattern[1][0];
+    (0..side).map(|y| (0..side).map(|x| match (cell(y), cell(x)) {
+        (Some(r), Some(c)) => if map.get(r, c) { LIT } else { DARK },
+        _ => LINE,
+    }).collect()).collect()
+}
 
-    r
+fn to_pgm(rows: &[Vec<u8>]) -> Vec<u8> {
+    let width = rows.first().map_or(0, |r| r.len());
+    let mut img = format!("P5\n{} {}\n255\n", width, rows.len()).into_bytes();
+    for row in rows {
+        img.extend_from_slice(row);
+    }
+    img
 }
 
-fn rotate3(pattern: &Map) -> Map {
-    let mut r = pattern.clone();
+// Black and white, anything darker than mid grey black. Gridlines are drawn
+// black too, or they would vanish next to unlit cells.
+fn to_pbm(rows: &[Vec<u8>]) -> Vec<u8> {
+    let width = rows.first().map_or(0, |r| r.len());
+    let mut img = format!("P4\n{} {}\n", width, rows.len()).into_bytes();
+    let black = |v: u8| v < 128 || v == LINE;
+    for row in rows {
+        for byte in row.chunks(8) {
+            let bits = byte.iter().enumerate().fold(0u8, |b, (i, &v)| if black(v) { b | 0x80 >> i } else { b });
+            img.push(bits);
+        }
+    }
+    img
+}
 
-    // 1 2 3 
-    // 4 5 6
-    // 7 8 9
-    //
-    // 3 6 9
-    // 2 5 8
-    // 1 4 7
+// Iterations 0 to iters next to each other, top aligned, each with lines
+// around the blocks the next iteration splits it into.
+fn sheet(start: &Map, book: &Book, iters: usize, scale: usize) -> Result<Vec<Vec<u8>>, RuleProblem> {
+    let gap = 2 * scale;
+    let mut map = BitGrid::from_map(start);
+    let mut panels = Vec::new();
+    for i in 0..iters + 1 {
+        panels.push(raster(&map, scale, book.step(map.size).ok()));
+        if i < iters {
+            map = enhance(map, book, 1)?;
+        }
+    }
 
-    r[0][0] = pattern[0][2];
-    r[0][1] = pattern[1][2];
-    r[0][2] = pattern[2][2];
+    let height = panels.iter().map(|p| p.len()).max().unwrap_or(0) + 2 * gap;
+    let mut rows = vec![vec![GAP; gap]; height];
+    for panel in &panels {
+        for (y, row) in rows.iter_mut().enumerate() {
+            match panel.get(y.wrapping_sub(gap)) {
+                Some(line) => row.extend_from_slice(line),
+                None => row.extend(std::iter::repeat_n(GAP, panel.len())),
+            }
+            row.extend(std::iter::repeat_n(GAP, gap));
+        }
+    }
+    Ok(rows)
+}
 
-    r[1][0] = pattern[0][1];
-    r[1][2] = pattern[2][1];
+fn write_image(file: &str, rows: &[Vec<u8>]) {
+    if file.ends_with(".pbm") {
+        fs::write(file, to_pbm(rows)).unwrap();
+    }
+    else {
+        fs::write(file, to_pgm(rows)).unwrap();
+    }
+}
+
+// Rules, and optionally a start pattern on a line of its own.
+fn parse_input(input: &str) -> (Option<Map>, Vec<Rule>) {
+    let mut start = None;
+    let mut rules = Vec::new();
+    for (i, row) in input.lines().enumerate() {
+        let row = row.trim();
+        if row.is_empty() {
+            continue;
+        }
+        if row.contains(" => ") {
+            rules.push(parse_rule(row));
+        }
+        else if start.is_none() {
+            start = Some(parse_map(row));
+        }
+        else {
+            panic!("line {}: second start pattern", i + 1);
+        }
+    }
+    (start, rules)
+}
+
+pub fn run() {
+    let args: Vec<String> = env::args().skip(2).collect();
+    let mut file = "day21.txt".to_string();
+    let mut start = None;
+    let mut split = DEFAULT_SPLIT.to_vec();
+    let mut count = None;
+    let mut iters = None;
+    let mut image = None;
+    let mut sheet_file = None;
+    let mut scale = 4;
+    let mut lines = false;
+    for pair in args.chunks(2) {
+        match (pair[0].as_str(), pair.get(1)) {
+            ("file", Some(f)) => file = f.clone(),
+            ("start", Some(p)) => start = Some(parse_map(p)),
+            ("split", Some(ks)) => split = ks.split(',').map(|k| k.parse().unwrap()).collect(),
+            ("count", Some(n)) => count = Some(n.parse().unwrap()),
+            ("iters", Some(n)) => iters = Some(n.parse().unwrap()),
+            ("image", Some(f)) => image = Some(f.clone()),
+            ("sheet", Some(f)) => sheet_file = Some(f.clone()),
+            ("scale", Some(n)) => scale = n.parse().unwrap(),
+            ("lines", Some(on)) => lines = on == "on",
+            _ => panic!("usage: 21 [file F] [start P] [split K,..] [count N | iters N] \
+                         [image F.pbm|F.pgm [lines on]] [sheet F] [scale S]"),
+        }
+    }
 
-    r[2][0] = pattern[0][0];
-    r[2][1] = pattern[1][0];
-    r[2][2] = pattern[2][0];
+    let (input_start, rules) = parse_input(&fs::read_to_string(file).unwrap());
+    let start = start.or(input_start).unwrap_or_else(start_map);
+    let problems = validate(&rules, &split);
+    if !problems.is_empty() {
+        for p in problems {
+            println!("aoc21: {}", p);
+        }
+        return;
+    }
 
+    if let Some(n) = count {
+        if split != DEFAULT_SPLIT {
+            panic!("counting needs the default split");
+        }
+        match count_lit(&start, &rules, n) {
+            Ok(lit) => println!("aoc21 after {} iterations: {}", n, lit),
+            Err(p) => println!("aoc21: {}", p),
+        }
+        return;
+    }
+    let book = Book::new(&rules, &split);
+    let lit = |n| enhance(BitGrid::from_map(&start), &book, n).map(|m| m.count());
+    // validate can't tell whether every size the map grows to splits, or
+    // check the large blocks, so problems may still turn up on the way
+    let done = if let Some(file) = image {
+        enhance(BitGrid::from_map(&start), &book, iters.unwrap_or(5)).map(|map| {
+            let block = if lines { book.step(map.size).ok() } else { None };
+            write_image(&file, &raster(&map, scale, block));
+        })
+    }
+    else if let Some(file) = sheet_file {
+        sheet(&start, &book, iters.unwrap_or(5), scale).map(|rows| write_image(&file, &rows))
+    }
+    else {
+        match iters {
+            Some(n) => lit(n).map(|c| println!("aoc21 after {} iterations: {}", n, c)),
+            None => lit(5).map(|c| println!("aoc21-1: {}", c))
+                .and_then(|_| lit(18).map(|c| println!("aoc21-2: {}", c))),
+        }
+    };
+    if let Err(p) = done {
+        println!("aoc21: {}", p);
+    }
+}
 
-    r
+// A quarter turn anticlockwise:
+//
+// 1 2 3    3 6 9
+// 4 5 6    2 5 8
+// 7 8 9    1 4 7
+fn rotate(pattern: &Map) -> Map {
+    let n = pattern.len();
+    (0..n).map(|r| (0..n).map(|c| pattern[c][n - 1 - r]).collect()).collect()
 }
 
 fn flip(pattern: &Map) -> Map {
@@ -237,25 +630,21 @@ fn flip(pattern: &Map) -> Map {
     f
 }
 
-fn print_map(m: &Map) {
-    println!("");
-    for row in m.iter() {
-        for c in row.iter() {
-            if *c {
-                print!("#");
-            }
-            else {
-                print!(".");
-            }
-        }
-        println!("");
-    }
+fn format_map(m: &Map) -> String {
+    let rows: Vec<String> = m.iter().map(|row| row.iter().map(|&c| if c { '#' } else { '.' }).collect()).collect();
+    rows.join("/")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn run_1(rules: &[Rule], iters: usize) -> Result<usize, RuleProblem> {
+        let book = Book::new(rules, &DEFAULT_SPLIT);
+        Ok(enhance(BitGrid::from_map(&start_map()), &book, iters)?.count())
+    }
+
+
     #[test]
     fn aoc21_parse_rule() {
         assert_eq!(parse_rule("../.# => ##./#../..."), 
@@ -266,7 +655,7 @@ mod tests {
     }
 
     #[test]
-    fn aoc21_check_match() {
+    fn aoc21_encode() {
         // * *
         //  *
         // * *
@@ -283,20 +672,21 @@ mod tests {
             vec!{ false, true },
         ];
 
-        assert_eq!(true, is_match(&t1, &vv, 0, 0));
-        assert_eq!(true, is_match(&t1, &vv, 1, 1));
-        assert_eq!(false, is_match(&t1, &vv, 0, 1));
-        assert_eq!(false, is_match(&t1, &vv, 1, 0));
+        assert_eq!(0b1001, encode(&t1));
+        assert_eq!(0b101_010_101, encode(&vv));
+        assert_eq!(t1, decode(0b1001, 2));
+        assert_eq!(vv, decode(encode(&vv), 3));
+
+        let g = BitGrid::from_map(&vv);
+        assert_eq!(encode(&t1), g.block(0, 0, 2));
+        assert_eq!(encode(&t1), g.block(1, 1, 2));
+        assert!(encode(&t1) != g.block(0, 1, 2));
+        assert!(encode(&t1) != g.block(1, 0, 2));
+        assert_eq!(5, g.count());
     }
 
     #[test]
-    fn aoc21_check_set_pattern() {
-        let mut vv : Vec<Vec<bool>> = vec![
-            vec!{ false, false, false },
-            vec!{ false, false, false },
-            vec!{ false, false, false },
-        ];
-
+    fn aoc21_check_put() {
         // * 
         //  *
         let t1 : Vec<Vec<bool>> = vec![
@@ -304,13 +694,23 @@ mod tests {
             vec!{ false, true },
         ];
 
-        set_pattern(&t1, &mut vv, 0, 0);
-        set_pattern(&t1, &mut vv, 1, 1);
-
-        assert_eq!(true, is_match(&t1, &vv, 0, 0));
-        assert_eq!(true, is_match(&t1, &vv, 1, 1));
-        assert_eq!(false, is_match(&t1, &vv, 0, 1));
-        assert_eq!(false, is_match(&t1, &vv, 1, 0));
+        let mut g = BitGrid::new(3);
+        g.put(0, 0, 2, encode(&t1));
+        g.put(1, 1, 2, encode(&t1));
+
+        assert_eq!(encode(&t1), g.block(0, 0, 2));
+        assert_eq!(encode(&t1), g.block(1, 1, 2));
+        assert!(encode(&t1) != g.block(0, 1, 2));
+        assert!(encode(&t1) != g.block(1, 0, 2));
+
+        // rows wider than a word
+        let mut g = BitGrid::new(130);
+        g.set(129, 129);
+        g.set(0, 64);
+        assert!(g.get(129, 129) && g.get(0, 64) && !g.get(0, 63));
+        assert_eq!(2, g.count());
+        g.reset(4);
+        assert_eq!(BitGrid::new(4), g);
     }
 
     #[test]
@@ -325,9 +725,7 @@ mod tests {
             vec!{ true, false },
         ];
 
-        let r1 = rotate(&t1);
-
-        assert_eq!(true, is_match(&r1, &t2, 0, 0));
+        assert_eq!(t2, rotate(&t1));
     }
 
     #[test]
@@ -346,7 +744,7 @@ mod tests {
 
         let r1 = rotate(&t1);
 
-        assert_eq!(true, is_match(&r1, &t1_r, 0, 0));
+        assert_eq!(t1_r, r1);
 
         let r2 = rotate(&r1);
 
@@ -356,7 +754,7 @@ mod tests {
             vec!{ false, false, true },
         ];
 
-        assert_eq!(true, is_match(&r2, &t3, 0, 0));
+        assert_eq!(t3, r2);
 
         let t4 = vec![
             vec!{ false, true, false},
@@ -370,7 +768,7 @@ mod tests {
             vec!{ false, false, true },
         ];
 
-        assert_eq!(true, is_match(&t4_r, &rotate(&t4), 0, 0));
+        assert_eq!(t4_r, rotate(&t4));
     }
 
     #[test]
@@ -387,7 +785,7 @@ mod tests {
             vec!{ true, false, false },
         ];
         
-        assert_eq!(true, is_match(&flip(&t1), &t1_f, 0, 0));
+        assert_eq!(t1_f, flip(&t1));
 
         let t2 : Vec<Vec<bool>> = vec![
             vec!{ true, false },
@@ -400,7 +798,7 @@ mod tests {
         ];
 
 
-        assert_eq!(true, is_match(&flip(&t2), &t2_f, 0, 0));
+        assert_eq!(t2_f, flip(&t2));
     }
 
     #[test]
@@ -409,13 +807,207 @@ mod tests {
 .#./..#/### => #..#/..../..../#..#";
         let rules : Vec<Rule> = input.lines().map(parse_rule).collect();
 
-        for rule in &rules {
-            for s in &rule.sources {
-                print_map(s);
-                println!("-----------------");
+        assert_eq!(Ok(12), run_1(&rules, 2));
+        assert_eq!(Err("no rule for ##/#.".to_string()), run_1(&rules, 3).map_err(|e| e.to_string()));
+        assert_eq!(".#./..#/###", format_map(&start_map()));
+    }
+
+    // A rule for every pattern, the same for all orientations of it.
+    fn full_rules() -> Vec<Rule> {
+        let mut rules = Vec::new();
+        for size in 2..4 {
+            for bits in 0..1 << (size * size) {
+                let rule = Rule::new(decode(bits, size), Map::new());
+                if rule.sources.iter().map(encode).min() != Some(bits) {
+                    continue;
+                }
+                let out = (bits * 2654435761 + 12345) % (1 << ((size + 1) * (size + 1)));
+                rules.push(Rule::new(decode(bits, size), decode(out, size + 1)));
             }
         }
+        rules
+    }
+
+    #[test]
+    fn aoc21_big() {
+        let mut n = Big::from(999_999_999);
+        n.add(&Big::from(1));
+        assert_eq!("1000000000", n.to_string());
+        assert_eq!("0", Big::default().to_string());
+        assert_eq!("0", n.times(0).to_string());
+        let mut m = Big::from(u64::MAX);
+        m.add(&m.clone());
+        assert_eq!((u64::MAX as u128 * 2).to_string(), m.to_string());
+        assert_eq!((u64::MAX as u128 * 2 * 1_000_003).to_string(), m.times(1_000_003).to_string());
+    }
 
-        assert_eq!(12, run_1(&rules, 2));
+    #[test]
+    fn aoc21_count_matches_expansion() {
+        let rules = full_rules();
+        for iters in 0..12 {
+            assert_eq!(Big::from(run_1(&rules, iters).unwrap() as u64), count_lit(&start_map(), &rules, iters).unwrap());
+        }
+
+        // starting from an even map the first few iterations are done directly
+        let start = parse_map("#..#/.##./..../##..");
+        let book = Book::new(&rules, &DEFAULT_SPLIT);
+        let mut map = BitGrid::from_map(&start);
+        let mut next = BitGrid::new(0);
+        for iters in 0..9 {
+            assert_eq!(Ok(Big::from(map.count() as u64)), count_lit(&start, &rules, iters));
+            expand(&map, &book, &mut next).unwrap();
+            std::mem::swap(&mut map, &mut next);
+        }
+
+        assert!(count_lit(&start_map(), &rules, 300).unwrap().to_string().len() > 90);
+
+        // an odd start that isn't made of 3x3 blocks
+        let odd = parse_map("#.#.#/...../...../...../.....");
+        assert_eq!(Err(RuleProblem::Unsplittable(5)), count_lit(&odd, &rules, 5));
+    }
+
+    #[test]
+    fn aoc21_orientations() {
+        assert_eq!(1, orientations(&parse_map("../..")).len());
+        assert_eq!(4, orientations(&parse_map("#./..")).len());
+        assert_eq!(2, orientations(&parse_map("#./.#")).len());
+        assert_eq!(8, orientations(&parse_map(".#./..#/###")).len());
+        assert_eq!(1, orientations(&parse_map(".#./###/.#.")).len());
+
+        // any size, and four quarter turns get back to the start
+        let p = parse_map("#.../.#../..##/....");
+        assert_eq!(8, orientations(&p).len());
+        assert_eq!(p, rotate(&rotate(&rotate(&rotate(&p)))));
+        assert_eq!(parse_map("..#./..#./.#../#..."), rotate(&p));
+
+        // 2 patterns of size 1, 6 of size 2 and 102 of size 3 up to symmetry
+        for &(size, classes) in &[(1, 2), (2, 6), (3, 102)] {
+            let n = (0..1 << (size * size)).filter(|&b| {
+                let m = decode(b, size);
+                canonical(&m) == m
+            }).count();
+            assert_eq!(classes, n);
+        }
+    }
+
+    #[test]
+    fn aoc21_validate() {
+        let rules = full_rules();
+        assert_eq!(Vec::<RuleProblem>::new(), validate(&rules, &[2, 3]));
+
+        let input = "../.# => ##./#../...\n.#./..#/### => #..#/..../..../#..#\n#./.. => .../.../...\n.#/.. => ##./#../...\n.##/#.#/..# => #..#/..../..../#..#\n.#/.. => .#./.../...";
+        let rules: Vec<Rule> = input.lines().map(parse_rule).collect();
+        let problems = validate(&rules, &[2]);
+        assert_eq!(vec![
+                   RuleProblem::Conflict(0, 2),
+                   RuleProblem::Duplicate(0, 3),
+                   RuleProblem::Duplicate(1, 4),
+                   RuleProblem::Conflict(0, 5),
+                   RuleProblem::Missing(parse_map("../..")),
+                   RuleProblem::Missing(parse_map(".#/#.")),
+                   RuleProblem::Missing(parse_map("../##")),
+                   RuleProblem::Missing(parse_map(".#/##")),
+                   RuleProblem::Missing(parse_map("##/##")),
+                   ], problems);
+        assert_eq!("rule 4 repeats rule 1", problems[1].to_string());
+        assert_eq!("rules 1 and 3 match the same pattern with different outputs", problems[0].to_string());
+        assert_eq!("no rule for ../##", problems[6].to_string());
+
+        let rules = vec![parse_rule("../.. => ../.."), parse_rule("#./.. => #../.../..")];
+        assert_eq!(vec![RuleProblem::BadSize(0), RuleProblem::BadSize(1)], validate(&rules, &[]));
+        assert_eq!("rule 2 doesn't turn a square into one a size larger", RuleProblem::BadSize(1).to_string());
+
+        // a ragged source is reported, not rotated
+        let rules = vec![parse_rule("#./. => ##./#../..."), parse_rule("../.# => ##./#../...")];
+        assert_eq!(vec![RuleProblem::BadSize(0)], validate(&rules, &[]));
+    }
+
+    #[test]
+    fn aoc21_parse_input() {
+        let (start, rules) = parse_input("\n#./.#\n../.# => ##./#../...\n\n.#./..#/### => #..#/..../..../#..#\n");
+        assert_eq!(Some(parse_map("#./.#")), start);
+        assert_eq!(2, rules.len());
+
+        let (start, rules) = parse_input("../.# => ##./#../...");
+        assert_eq!(None, start);
+        assert_eq!(1, rules.len());
+    }
+
+    #[test]
+    fn aoc21_split() {
+        let book = Book::new(&[], &[3, 2]);
+        assert_eq!(Ok(3), book.step(6));
+        assert_eq!(Ok(2), book.step(4));
+        assert_eq!(Err(RuleProblem::Unsplittable(5)), book.step(5));
+        assert_eq!(Ok(2), Book::new(&[], &DEFAULT_SPLIT).step(6));
+        assert_eq!("a 5x5 map can't be split into blocks of any rule size", RuleProblem::Unsplittable(5).to_string());
+
+        // single cells growing into 2x2 squares
+        let rules = vec![parse_rule("# => ##/##"), parse_rule(". => ../..")];
+        assert!(validate(&rules, &[1]).is_empty());
+        let book = Book::new(&rules, &[1]);
+        let map = enhance(BitGrid::from_map(&parse_map("#.")), &Book::new(&rules, &[1]), 0).unwrap();
+        assert_eq!(1, map.count());
+        let map = enhance(BitGrid::from_map(&parse_map("#./..")), &book, 4).unwrap();
+        assert_eq!((32, 256), (map.size, map.count()));
+    }
+
+    #[test]
+    fn aoc21_large_blocks() {
+        let rules = vec![
+            parse_rule("..../..../..../.... => #..../...../...../...../....."),
+            parse_rule("#..../...../...../...../..... => ######/....../....../....../....../......"),
+        ];
+        assert!(validate(&rules, &[]).is_empty());
+        // all but one of the 8548 kinds of 4x4 pattern are missing, 5x5 ones
+        // aren't checked
+        assert_eq!(8547, validate(&rules, &[4, 5]).len());
+
+        let start = BitGrid::from_map(&parse_map("..../..../..../...."));
+        let book = Book::new(&rules, &[4, 5]);
+        assert_eq!(1, enhance(start.clone(), &book, 1).unwrap().count());
+        assert_eq!(6, enhance(start.clone(), &book, 2).unwrap().count());
+        // the 6x6 map splits into neither 4x4 nor 5x5 blocks
+        assert_eq!(Err(RuleProblem::Unsplittable(6)), enhance(start.clone(), &book, 3).map(|m| m.count()));
+        assert_eq!(Err(RuleProblem::Unsplittable(5)), enhance(start, &Book::new(&rules, &[4]), 2).map(|m| m.count()));
+
+        let blank = |k: usize| vec![".".repeat(k); k].join("/");
+        assert!(validate(&[parse_rule(&format!("{} => {}", blank(10), blank(11)))], &[]).is_empty());
+        let too_big = format!("{} => {}", blank(11), blank(12));
+        assert_eq!(vec![RuleProblem::BadSize(0)], validate(&[parse_rule(&too_big)], &[]));
+    }
+
+    #[test]
+    fn aoc21_images() {
+        let map = BitGrid::from_map(&parse_map("#./.#"));
+        let plain = raster(&map, 2, None);
+        assert_eq!(vec![
+                   vec![LIT, LIT, DARK, DARK],
+                   vec![LIT, LIT, DARK, DARK],
+                   vec![DARK, DARK, LIT, LIT],
+                   vec![DARK, DARK, LIT, LIT],
+                   ], plain);
+        assert_eq!(b"P5\n4 4\n255\n".to_vec(), to_pgm(&plain)[..11].to_vec());
+        assert_eq!(b"P4\n4 4\n\xc0\xc0\x30\x30".to_vec(), to_pbm(&plain));
+
+        let map = BitGrid::from_map(&parse_map("#..#/..../..../...."));
+        let lined = raster(&map, 1, Some(2));
+        assert_eq!(7, lined.len());
+        assert_eq!(vec![LINE; 7], lined[0]);
+        assert_eq!(vec![LINE, LIT, DARK, LINE, DARK, LIT, LINE], lined[1]);
+        assert_eq!(vec![LINE; 7], lined[3]);
+        // lines are black in PBM, also next to unlit cells
+        assert_eq!(b"P4\n7 7\n\xfe\xd6\x92\xfe\x92\x92\xfe".to_vec(), to_pbm(&lined));
+
+        // panels of 3, 4 and 6 cells with lines around 3x3, 2x2 and 2x2 blocks
+        let input = "../.# => ##./#../...\n.#./..#/### => #..#/..../..../#..#";
+        let rules: Vec<Rule> = input.lines().map(parse_rule).collect();
+        let book = Book::new(&rules, &DEFAULT_SPLIT);
+        let rows = sheet(&start_map(), &book, 2, 1).unwrap();
+        assert_eq!((6 + 4) + 2 * 2, rows.len());
+        assert_eq!(2 + (3 + 2) + 2 + (4 + 3) + 2 + (6 + 4) + 2, rows[0].len());
+        assert!(rows.iter().all(|r| r.len() == rows[0].len()));
+        assert_eq!(vec![LINE; 5], rows[2][2..7].to_vec());
+        assert_eq!(Err(RuleProblem::Missing(parse_map("##/#."))), sheet(&start_map(), &book, 3, 1).map(|r| r.len()));
     }
 }
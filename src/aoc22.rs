@@ -1,254 +1,562 @@
-use std::collections::{HashSet, HashMap};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
 
-#[derive(Debug, Clone)]
-enum Direction {
-    Up, 
-    Down,
-    Left, 
-    Right
+use std::env;
+use grid::{self, Direction, GrowGrid, Pos, SparseGrid};
+
+// One line per cell state: the state, the state it becomes, how the carrier
+// turns and optionally `infect` when the burst counts as an infection. The
+// first state is the one of untouched cells.
+const PART_1: &str = "\
+. # left infect
+# . right";
+
+const PART_2: &str = "\
+. W left
+W # none infect
+# F right
+F . reverse";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Turn {
+    Left,
+    Right,
+    None,
+    Reverse,
 }
 
-impl Direction {
-    fn delta(&self) -> (isize, isize) {
-        match self {
-            Direction::Down => (1, 0), 
-            Direction::Up => (-1, 0),
-            Direction::Left => (0, -1),
-            Direction::Right => (0, 1),
+impl Turn {
+    fn apply(&self, dir: Direction) -> Direction {
+        match *self {
+            Turn::Left => dir.turn_left(),
+            Turn::Right => dir.turn_right(),
+            Turn::None => dir,
+            Turn::Reverse => dir.turn_around(),
         }
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rule {
+    next: u8,
+    turn: Turn,
+    infects: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rules {
+    names: Vec<char>,
+    rules: Vec<Rule>,
+}
 
-    fn turn_left(&self) -> Self {
-        match self {
-            Direction::Up => Direction::Left,
-            Direction::Down => Direction::Right,
-            Direction::Left => Direction::Down,
-            Direction::Right => Direction::Up,
+impl Rules {
+    fn state(&self, c: char) -> u8 {
+        match self.names.iter().position(|&n| n == c) {
+            Some(i) => i as u8,
+            None => panic!("Unknown cell state: {:?}", c),
         }
     }
+}
 
-    fn turn_right(&self) -> Self {
-        match self {
-            Direction::Up => Direction::Right,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-            Direction::Right => Direction::Down,
+fn parse_rules(s: &str) -> Rules {
+    let mut lines = Vec::new();
+    for row in s.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with("//")) {
+        let words: Vec<&str> = row.split_whitespace().collect();
+        let one_char = |w: &str| {
+            let mut cs = w.chars();
+            match (cs.next(), cs.next()) {
+                (Some(c), None) => c,
+                _ => panic!("Cell states are single characters: {:?}", row),
+            }
+        };
+        if words.len() < 3 || words.len() > 4 {
+            panic!("Can't parse rule: {:?}", row);
         }
+        let turn = match words[2] {
+            "left" => Turn::Left,
+            "right" => Turn::Right,
+            "none" => Turn::None,
+            "reverse" => Turn::Reverse,
+            t => panic!("Unknown turn {:?} in {:?}", t, row),
+        };
+        let infects = match words.get(3) {
+            None => false,
+            Some(&"infect") => true,
+            Some(w) => panic!("Unknown flag {:?} in {:?}", w, row),
+        };
+        lines.push((one_char(words[0]), one_char(words[1]), turn, infects));
     }
 
-    fn turn_around(&self) -> Self {
-        match self {
-            Direction::Up => Direction::Down,
-            Direction::Down => Direction::Up,
-            Direction::Left => Direction::Right,
-            Direction::Right => Direction::Left,
+    let names: Vec<char> = lines.iter().map(|l| l.0).collect();
+    if names.len() > 256 {
+        panic!("At most 256 cell states, got {}", names.len());
+    }
+    for (i, c) in names.iter().enumerate() {
+        if names[..i].contains(c) {
+            panic!("Two rules for cell state {:?}", c);
         }
     }
+    let mut r = Rules {
+        names,
+        rules: Vec::new(),
+    };
+    r.rules = lines.iter().map(|&(_, next, turn, infects)| Rule {
+        next: r.state(next),
+        turn,
+        infects,
+    }).collect();
+    r
+}
+
+// Renumbers the rule sets of several carriers so they agree on what each cell
+// state byte means. A carrier leaves states its own rules don't know alone.
+fn align_rules(sets: &[Rules]) -> Vec<Rules> {
+    let mut names: Vec<char> = Vec::new();
+    for rules in sets {
+        if !names.is_empty() && rules.names[0] != names[0] {
+            panic!("Rule sets disagree on the clean state: {:?} vs {:?}", names[0], rules.names[0]);
+        }
+        for &c in &rules.names {
+            if !names.contains(&c) {
+                names.push(c);
+            }
+        }
+    }
+    if names.len() > 256 {
+        panic!("At most 256 cell states, got {}", names.len());
+    }
+
+    sets.iter().map(|rules| {
+        let idx = |c: char| names.iter().position(|&n| n == c).unwrap() as u8;
+        let aligned = names.iter().enumerate().map(|(i, &c)| {
+            match rules.names.iter().position(|&n| n == c) {
+                Some(own) => {
+                    let r = rules.rules[own];
+                    Rule {
+                        next: idx(rules.names[r.next as usize]),
+                        ..r
+                    }
+                },
+                None => Rule { next: i as u8, turn: Turn::None, infects: false },
+            }
+        }).collect();
+        Rules {
+            names: names.clone(),
+            rules: aligned,
+        }
+    }).collect()
+}
+
+type Map = SparseGrid<u8>;
+
+fn parse_map(s: &str, rules: &Rules) -> (Pos, Map) {
+    let map = Map::parse(s, |c| match rules.state(c) {
+        0 => None,
+        n => Some(n),
+    });
+    (start_pos(s), map)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Pos {
-    row: isize,
-    col: isize,
+// Storage for the map, either the sparse hash map or a dense grid with one
+// byte per cell. Untouched cells are in state 0.
+trait Cells {
+    fn from_map(map: Map) -> Self;
+    fn get(&self, p: Pos) -> u8;
+    fn set(&mut self, p: Pos, s: u8);
 }
 
-impl Pos {
-    fn new(row: isize, col: isize) -> Self {
-        Pos {
-            row: row,
-            col: col,
+impl Cells for Map {
+    fn from_map(map: Map) -> Self {
+        map
+    }
+
+    fn get(&self, p: Pos) -> u8 {
+        self.get(&p).cloned().unwrap_or(0)
+    }
+
+    fn set(&mut self, p: Pos, s: u8) {
+        if s == 0 {
+            self.remove(&p);
+        }
+        else {
+            self.insert(p, s);
         }
     }
 }
 
-type Map = HashSet<Pos>;
+type DenseMap = GrowGrid<u8>;
+
+impl Cells for DenseMap {
+    fn from_map(map: Map) -> Self {
+        let mut g = GrowGrid::new(0);
+        for (p, s) in map.iter() {
+            *g.get_mut(*p) = *s;
+        }
+        g
+    }
 
-struct State {
+    fn get(&self, p: Pos) -> u8 {
+        *self.get(p)
+    }
+
+    fn set(&mut self, p: Pos, s: u8) {
+        *self.get_mut(p) = s;
+    }
+}
+
+// What happens when carriers meet. With `Share` they ignore each other,
+// with `Block` a carrier doesn't move onto a cell holding another carrier and
+// with `Yield` a carrier that starts the round on the same cell as one earlier
+// in the order skips its burst.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Conflict {
+    Share,
+    Block,
+    Yield,
+}
+
+#[derive(Debug)]
+struct Carrier<'a> {
     pos: Pos,
     dir: Direction,
     infected: usize,
-    map: Map,
+    rules: &'a Rules,
 }
 
-impl State {
-    fn new(pos: Pos, map: Map) -> Self {
-        State {
-            pos: pos,
-            dir: Direction::Up,
+impl<'a> Carrier<'a> {
+    fn new(pos: Pos, dir: Direction, rules: &'a Rules) -> Self {
+        Carrier {
+            pos,
+            dir,
             infected: 0,
-            map: map,
+            rules,
         }
     }
+}
 
-    fn is_clean(&self) -> bool {
-        !self.map.contains(&self.pos)
-    }
+#[derive(Debug)]
+struct World<'a, M> {
+    map: M,
+    carriers: Vec<Carrier<'a>>,
+    conflict: Conflict,
+}
 
-    fn step (&mut self) {
-        let new_dir = if self.is_clean() {
-            self.infected += 1;
-            self.map.insert(self.pos.clone());
-            self.dir.turn_left()
+impl<'a, M: Cells> World<'a, M> {
+    fn new(map: M, carriers: Vec<Carrier<'a>>, conflict: Conflict) -> Self {
+        World {
+            map,
+            carriers,
+            conflict,
         }
-        else {
-            self.map.remove(&self.pos);
-            self.dir.turn_right()
-        };
+    }
+
+    // Every carrier bursts once, in order.
+    fn step(&mut self) {
+        let start: Vec<Pos> = self.carriers.iter().map(|c| c.pos).collect();
+        for i in 0..self.carriers.len() {
+            let pos = self.carriers[i].pos;
+            if self.conflict == Conflict::Yield && start[..i].contains(&pos) {
+                continue;
+            }
 
-        self.dir = new_dir;
+            let rule = self.carriers[i].rules.rules[self.map.get(pos) as usize];
+            self.map.set(pos, rule.next);
 
-        let (dr, dc) = self.dir.delta();
-        // println!("dir: {:?} ({}, {})", self.dir, dr, dc);
-        self.pos.row += dr;
-        self.pos.col += dc;
+            let dir = rule.turn.apply(self.carriers[i].dir);
+            let next = pos.step(dir);
+            let blocked = self.conflict == Conflict::Block
+                && self.carriers.iter().any(|c| c.pos == next);
+
+            let c = &mut self.carriers[i];
+            if rule.infects {
+                c.infected += 1;
+            }
+            c.dir = dir;
+            if !blocked {
+                c.pos = next;
+            }
+        }
     }
 }
 
-fn parse_map(s: &str) -> (Pos, Map) {
-    let mut map = Map::new(); 
-    let mut height = 0;
-    let mut width = 0;
-    for (r, row) in s.lines().enumerate() {
-        height = r + 1;
-        for (c, col) in row.chars().enumerate() {
-            width = c + 1;
-            if col == '#' {
-                map.insert(Pos::new(r as isize, c as isize));
+fn dir_name(dir: Direction) -> &'static str {
+    match dir {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+// Colours of the cell states in exported images, carriers are red.
+const PALETTE: [[u8; 3]; 6] = [
+    [255, 255, 255],
+    [40, 40, 40],
+    [240, 200, 60],
+    [60, 120, 220],
+    [80, 180, 90],
+    [170, 90, 200],
+];
+
+impl<'a, M: Cells> World<'a, M> {
+    // Like the puzzle text, with brackets around the cells holding a carrier.
+    fn render(&self, lo: Pos, hi: Pos) -> String {
+        let names = &self.carriers[0].rules.names;
+        let has_carrier = |p: Pos| self.carriers.iter().any(|c| c.pos == p);
+        let mut s = String::new();
+        for r in lo.row..hi.row + 1 {
+            for c in lo.col..hi.col + 1 {
+                let left = c > lo.col && has_carrier(Pos::new(r, c - 1));
+                let here = has_carrier(Pos::new(r, c));
+                match (left, here) {
+                    (true, true) => s.push('|'),
+                    (true, false) => s.push(']'),
+                    (false, true) => s.push('['),
+                    (false, false) if c > lo.col => s.push(' '),
+                    _ => (),
+                }
+                s.push(names[self.map.get(Pos::new(r, c)) as usize]);
+            }
+            if has_carrier(Pos::new(r, hi.col)) {
+                s.push(']');
             }
+            s.push('\n');
         }
+        s
     }
-    (Pos::new((height/2) as isize, (width/2) as isize), map)
-}
 
-fn parse_map2(s: &str) -> (Pos, Map2) {
-    let mut map = Map2::new(); 
-    let mut height = 0;
-    let mut width = 0;
-    for (r, row) in s.lines().enumerate() {
-        height = r + 1;
-        for (c, col) in row.chars().enumerate() {
-            width = c + 1;
-            if col == '#' {
-                map.insert(Pos::new(r as isize, c as isize),
-                    CellState::Infected);
+    fn frame(&self, burst: usize, lo: Pos, hi: Pos) -> String {
+        let mut s = format!("burst {}:", burst);
+        for c in &self.carriers {
+            s += &format!(" ({}, {}) {}", c.pos.row, c.pos.col, dir_name(c.dir));
+        }
+        s + "\n" + &self.render(lo, hi)
+    }
+
+    fn to_ppm(&self, lo: Pos, hi: Pos, scale: usize) -> Vec<u8> {
+        let width = (hi.col - lo.col + 1) as usize;
+        let height = (hi.row - lo.row + 1) as usize;
+        let mut img = format!("P6\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+        for r in lo.row..hi.row + 1 {
+            let mut row = Vec::with_capacity(width * scale * 3);
+            for c in lo.col..hi.col + 1 {
+                let p = Pos::new(r, c);
+                let colour = if self.carriers.iter().any(|c| c.pos == p) {
+                    [220, 30, 30]
+                }
+                else {
+                    PALETTE[self.map.get(p) as usize % PALETTE.len()]
+                };
+                for _ in 0..scale {
+                    row.extend_from_slice(&colour);
+                }
+            }
+            for _ in 0..scale {
+                img.extend_from_slice(&row);
             }
         }
+        img
     }
-    (Pos::new((height/2) as isize, (width/2) as isize), map)
 }
 
-fn run_1(s: &str, iters: usize) -> usize {
-    let (start, map) = parse_map(s);
-    let mut state = State::new(start, map);
+fn json_string(s: &str) -> String {
+    let mut j = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => j += "\\\"",
+            '\\' => j += "\\\\",
+            '\n' => j += "\\n",
+            '\r' => j += "\\r",
+            c if (c as u32) < 0x20 => j += &format!("\\u{:04x}", c as u32),
+            c => j.push(c),
+        }
+    }
+    j.push('"');
+    j
+}
 
-    for _ in 0..iters {
-        // println!("{:?} - {:?}", state.pos, state.dir);
-        state.step();
+// asciinema v2: a header line, then one [time, "o", text] event per frame.
+fn to_cast(frames: &[String], delay: f64) -> String {
+    let width = frames.iter().flat_map(|f| f.lines()).map(|l| l.chars().count()).max().unwrap_or(0);
+    let height = frames.iter().map(|f| f.lines().count()).max().unwrap_or(0);
+    let mut s = format!("{{\"version\": 2, \"width\": {}, \"height\": {}}}\n", width, height);
+    for (i, f) in frames.iter().enumerate() {
+        let text = String::from("\u{1b}[H\u{1b}[2J") + &f.replace('\n', "\r\n");
+        s += &format!("[{:.3}, \"o\", {}]\n", i as f64 * delay, json_string(&text));
     }
+    s
+}
 
-    state.infected
+// Runs the puzzle map with one carrier and writes a frame of the area within
+// `radius` of the carrier every `every` bursts, as PPM files in a directory or
+// as an asciinema cast. The window moves with the carrier.
+fn export_frames(s: &str, rules: &Rules, bursts: usize, every: usize, radius: isize, out: &str) {
+    let (start, map) = parse_map(s, rules);
+    let mut world = World::new(DenseMap::from_map(map), vec![Carrier::new(start, Direction::Up, rules)],
+                               Conflict::Share);
+
+    let mut frames = Vec::new();
+    for burst in 0..bursts + 1 {
+        if burst % every == 0 {
+            let at = world.carriers[0].pos;
+            let lo = Pos::new(at.row - radius, at.col - radius);
+            let hi = Pos::new(at.row + radius, at.col + radius);
+            if out.ends_with(".cast") {
+                frames.push(world.frame(burst, lo, hi));
+            }
+            else {
+                fs::create_dir_all(out).unwrap();
+                let file = format!("{}/frame_{:06}.ppm", out, burst / every);
+                fs::write(file, world.to_ppm(lo, hi, 4)).unwrap();
+            }
+        }
+        world.step();
+    }
+    if out.ends_with(".cast") {
+        fs::write(out, to_cast(&frames, 0.1)).unwrap();
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum CellState {
-    Weakened,
-    Infected,
-    Flagged,
+fn start_pos(s: &str) -> Pos {
+    let (height, width) = grid::text_size(s);
+    Pos::new((height/2) as isize, (width/2) as isize)
 }
 
-type Map2 = HashMap<Pos, CellState>;
+fn run_carriers<M: Cells>(s: &str, carriers: &[(Pos, Direction, Rules)], conflict: Conflict,
+                          iters: usize) -> Vec<usize> {
+    let sets: Vec<Rules> = carriers.iter().map(|c| c.2.clone()).collect();
+    let aligned = align_rules(&sets);
+    let (_, map) = parse_map(s, &aligned[0]);
 
-#[derive(Debug)]
-struct State2 {
-    pos: Pos,
-    dir: Direction,
-    infected: usize,
-    map: Map2,
-}
+    let cs = carriers.iter().zip(aligned.iter())
+        .map(|(c, rules)| Carrier::new(c.0, c.1, rules))
+        .collect();
+    let mut world = World::new(M::from_map(map), cs, conflict);
 
-impl State2 {
-    fn new(pos: Pos, map: Map2) -> Self {
-        State2 {
-            pos: pos,
-            dir: Direction::Up,
-            infected: 0,
-            map: map,
-        }
+    for _ in 0..iters {
+        world.step();
     }
 
-    fn is_clean(&self) -> bool {
-        !self.map.contains_key(&self.pos)
+    world.carriers.iter().map(|c| c.infected).collect()
+}
+
+fn run_rules<M: Cells>(s: &str, rules: &Rules, iters: usize) -> usize {
+    let (start, map) = parse_map(s, rules);
+    let mut world = World::new(M::from_map(map), vec![Carrier::new(start, Direction::Up, rules)],
+                               Conflict::Share);
+
+    for _ in 0..iters {
+        // println!("{:?} - {:?}", world.carriers[0].pos, world.carriers[0].dir);
+        world.step();
     }
 
-    fn step (&mut self) {
-        let new_dir = if self.is_clean() {
-            self.map.insert(self.pos.clone(), CellState::Weakened);
-            self.dir.turn_left()
+    world.carriers[0].infected
+}
+
+// One carrier per line: row, column, direction and rule file, where the rule
+// files `part1` and `part2` are the puzzle rules.
+fn parse_carriers(s: &str, read_rules: &dyn Fn(&str) -> Rules) -> Vec<(Pos, Direction, Rules)> {
+    s.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with("//")).map(|row| {
+        let words: Vec<&str> = row.split_whitespace().collect();
+        if words.len() != 4 {
+            panic!("Can't parse carrier: {:?}", row);
         }
-        else {
-            match self.map.get(&self.pos).unwrap() {
-                CellState::Weakened => {
-                    self.infected += 1;
-                    self.map.insert(self.pos.clone(), CellState::Infected);
-                    self.dir.clone()
-                },
-                CellState::Infected => {
-                    self.map.insert(self.pos.clone(), CellState::Flagged);
-                    self.dir.turn_right()
-                },
-                CellState::Flagged => {
-                    self.map.remove(&self.pos);
-                    self.dir.turn_around()
-                }
-            }
+        let dir = match words[2] {
+            "up" => Direction::Up,
+            "down" => Direction::Down,
+            "left" => Direction::Left,
+            "right" => Direction::Right,
+            d => panic!("Unknown direction {:?} in {:?}", d, row),
         };
+        let pos = Pos::new(words[0].parse().unwrap(), words[1].parse().unwrap());
+        (pos, dir, read_rules(words[3]))
+    }).collect()
+}
 
-        self.dir = new_dir;
-
-        let (dr, dc) = self.dir.delta();
-        // println!("dir: {:?} ({}, {})", self.dir, dr, dc);
-        self.pos.row += dr;
-        self.pos.col += dc;
+fn read_rules(name: &str) -> Rules {
+    match name {
+        "part1" => parse_rules(PART_1),
+        "part2" => parse_rules(PART_2),
+        file => parse_rules(&fs::read_to_string(file).unwrap()),
     }
 }
 
-fn run_2(s: &str, iters: usize) -> usize {
-    let (start, map) = parse_map2(s);
-    let mut state = State2::new(start, map);
-
-    for i in 0..iters {
-        if i % 10000 == 0 {
-            println!("{}", iters - i);
-        }
-        // println!("{:?} - {:?}", state.pos, state.dir);
-        state.step();
-    }
+fn run_1(s: &str, iters: usize) -> usize {
+    run_rules::<DenseMap>(s, &parse_rules(PART_1), iters)
+}
 
-    state.infected
+fn run_2(s: &str, iters: usize) -> usize {
+    run_rules::<DenseMap>(s, &parse_rules(PART_2), iters)
 }
 
 pub fn run() {
     let mut file = File::open("day22.txt").unwrap();
     let mut map = String::new();
     file.read_to_string(&mut map).unwrap();
-    println!("day22 - 1: {}", run_1(&map, 10000));
-    println!("day22 - 2: {}", run_2(&map, 10000000));
+
+    let args: Vec<String> = env::args().skip(2).collect();
+    let usage = "usage: 22 [rules RULES BURSTS] [carriers FILE BURSTS [share|block|yield]] \
+                 [frames RULES BURSTS EVERY RADIUS OUT] [hash]";
+    match args.first().map(|s| s.as_str()) {
+        Some("rules") => {
+            let (name, iters) = match (args.get(1), args.get(2)) {
+                (Some(name), Some(iters)) => (name, iters.parse().unwrap()),
+                _ => return println!("{}", usage),
+            };
+            println!("day22 - {}: {}", name, run_rules::<DenseMap>(&map, &read_rules(name), iters));
+        },
+        Some("carriers") => {
+            let (file, iters) = match (args.get(1), args.get(2)) {
+                (Some(file), Some(iters)) => (file, iters.parse().unwrap()),
+                _ => return println!("{}", usage),
+            };
+            let carriers = parse_carriers(&fs::read_to_string(file).unwrap(), &read_rules);
+            let conflict = match args.get(3).map(|s| s.as_str()) {
+                None | Some("share") => Conflict::Share,
+                Some("block") => Conflict::Block,
+                Some("yield") => Conflict::Yield,
+                Some(c) => panic!("Unknown conflict policy: {:?}", c),
+            };
+            let counts = run_carriers::<DenseMap>(&map, &carriers, conflict, iters);
+            for (i, n) in counts.iter().enumerate() {
+                println!("day22 - carrier {}: {}", i, n);
+            }
+        },
+        Some("frames") => {
+            let (name, bursts, every, radius, out) = match (args.get(1), args.get(2), args.get(3), args.get(4), args.get(5)) {
+                (Some(name), Some(bursts), Some(every), Some(radius), Some(out)) => (name, bursts, every, radius, out),
+                _ => return println!("{}", usage),
+            };
+            let every = every.parse().unwrap();
+            if every == 0 {
+                panic!("Frame interval must be at least 1 burst");
+            }
+            export_frames(&map, &read_rules(name), bursts.parse().unwrap(), every, radius.parse().unwrap(), out);
+        },
+        Some("hash") => {
+            println!("day22 - 1: {}", run_rules::<Map>(&map, &parse_rules(PART_1), 10000));
+            println!("day22 - 2: {}", run_rules::<Map>(&map, &parse_rules(PART_2), 10000000));
+        },
+        _ => {
+            println!("day22 - 1: {}", run_1(&map, 10000));
+            println!("day22 - 2: {}", run_2(&map, 10000000));
+        }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
     #[test]
     fn aoc22() {
         let m = r"..#
 #..
 ...";
-        let (start, map) = parse_map(m);
-        assert_eq!(true, map.contains(&Pos::new(0, 2)));
-        assert_eq!(true, map.contains(&Pos::new(1, 0)));
+        let (start, map) = parse_map(m, &parse_rules(PART_1));
+        assert!(map.get(&Pos::new(0, 2)).is_some());
+        assert!(map.get(&Pos::new(1, 0)).is_some());
         assert_eq!(Pos::new(1,1), start);
 
         assert_eq!(5, run_1(m, 7));
@@ -259,4 +567,171 @@ mod tests {
         assert_eq!(26, run_2(m, 100));
         assert_eq!(2511944, run_2(m, 10000000));
     }
+
+    #[test]
+    fn aoc22_parse_rules() {
+        let rules = parse_rules(PART_2);
+        assert_eq!(vec!['.', 'W', '#', 'F'], rules.names);
+        assert_eq!(Rule { next: 2, turn: Turn::None, infects: true }, rules.rules[1]);
+        assert_eq!(Rule { next: 0, turn: Turn::Reverse, infects: false }, rules.rules[3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown cell state: 'X'")]
+    fn aoc22_unknown_state() {
+        parse_rules(". X left");
+    }
+
+    #[test]
+    fn aoc22_langtons_ant() {
+        // the mirror image of part 1 on an empty map
+        let ant = parse_rules("// Langton's ant\n. # right infect\n# . left");
+        let m = ".....\n.....\n.....";
+        for &iters in &[10, 1000, 11000] {
+            assert_eq!(run_rules::<Map>(m, &parse_rules(PART_1), iters),
+                       run_rules::<Map>(m, &ant, iters));
+        }
+    }
+
+    #[test]
+    fn aoc22_align_rules() {
+        let aligned = align_rules(&[parse_rules(PART_1), parse_rules(PART_2)]);
+        assert_eq!(vec!['.', '#', 'W', 'F'], aligned[0].names);
+        assert_eq!(aligned[0].names, aligned[1].names);
+        assert_eq!(Rule { next: 2, turn: Turn::None, infects: false }, aligned[0].rules[2]);
+        assert_eq!(Rule { next: 1, turn: Turn::None, infects: true }, aligned[1].rules[2]);
+        assert_eq!(Rule { next: 3, turn: Turn::Right, infects: false }, aligned[1].rules[1]);
+    }
+
+    #[test]
+    fn aoc22_carriers() {
+        let m = "..#\n#..\n...";
+        let carriers = parse_carriers("1 1 up part1\n-500 0 left part2", &read_rules);
+        assert_eq!(Pos::new(-500, 0), carriers[1].0);
+        assert_eq!(Direction::Left, carriers[1].1);
+
+        // far apart the carriers don't see each other
+        for &conflict in &[Conflict::Share, Conflict::Block, Conflict::Yield] {
+            let counts = run_carriers::<Map>(m, &carriers, conflict, 70);
+            assert_eq!(vec![41, run_rules::<Map>(".", &parse_rules(PART_2), 70)], counts);
+        }
+        assert_eq!(vec![41], run_carriers::<DenseMap>(m, &carriers[..1], Conflict::Share, 70));
+    }
+
+    #[test]
+    fn aoc22_conflict() {
+        let rules = parse_rules(PART_1);
+        let twins = |conflict| {
+            let carriers = vec![
+                Carrier::new(Pos::new(0, 0), Direction::Up, &rules),
+                Carrier::new(Pos::new(0, 0), Direction::Up, &rules),
+            ];
+            let mut w = World::new(Map::new(), carriers, conflict);
+            w.step();
+            w
+        };
+
+        // the second carrier cleans the cell the first one infected
+        let w = twins(Conflict::Share);
+        assert_eq!(vec![1, 0], w.carriers.iter().map(|c| c.infected).collect::<Vec<_>>());
+        assert_eq!(0, w.map.iter().count());
+        assert_eq!(Pos::new(0, 1), w.carriers[1].pos);
+
+        let w = twins(Conflict::Yield);
+        assert_eq!(vec![1, 0], w.carriers.iter().map(|c| c.infected).collect::<Vec<_>>());
+        assert_eq!(1, w.map.iter().count());
+        assert_eq!(Pos::new(0, 0), w.carriers[1].pos);
+
+        // side by side, facing each other
+        let carriers = vec![
+            Carrier::new(Pos::new(0, 0), Direction::Down, &rules),
+            Carrier::new(Pos::new(0, 1), Direction::Up, &rules),
+        ];
+        let mut w = World::new(Map::new(), carriers, Conflict::Block);
+        w.step();
+        assert_eq!(Pos::new(0, 0), w.carriers[0].pos);
+        assert_eq!(Direction::Right, w.carriers[0].dir);
+        assert_eq!(Pos::new(0, 1), w.carriers[1].pos);
+        assert_eq!(Direction::Left, w.carriers[1].dir);
+        for _ in 0..1000 {
+            w.step();
+            assert!(w.carriers[0].pos != w.carriers[1].pos);
+        }
+    }
+
+    #[test]
+    fn aoc22_render() {
+        let m = "..#\n#..\n...";
+        let rules = parse_rules(PART_1);
+        let (start, map) = parse_map(m, &rules);
+        let mut w = World::new(map, vec![Carrier::new(start, Direction::Up, &rules)], Conflict::Share);
+        let (lo, hi) = (Pos::new(-3, -3), Pos::new(4, 5));
+
+        assert_eq!("\
+. . . . . . . . .
+. . . . . . . . .
+. . . . . . . . .
+. . . . . # . . .
+. . . #[.]. . . .
+. . . . . . . . .
+. . . . . . . . .
+. . . . . . . . .
+", w.render(lo, hi));
+
+        w.step();
+        assert_eq!("\
+. . . . . . . . .
+. . . . . . . . .
+. . . . . . . . .
+. . . . . # . . .
+. . .[#]# . . . .
+. . . . . . . . .
+. . . . . . . . .
+. . . . . . . . .
+", w.render(lo, hi));
+
+        assert_eq!("burst 1: (1, 0) left\n[#]#\n", w.frame(1, Pos::new(1, 0), Pos::new(1, 1)));
+        assert_eq!(".[#]\n", w.render(Pos::new(1, -1), Pos::new(1, 0)));
+
+        let img = w.to_ppm(lo, hi, 2);
+        assert!(img.starts_with(b"P6\n18 16\n255\n"));
+        assert_eq!(b"P6\n18 16\n255\n".len() + 18 * 16 * 3, img.len());
+    }
+
+    #[test]
+    fn aoc22_cast() {
+        let cast = to_cast(&["a\"b\n".to_string(), "cd\n\\\n".to_string()], 0.5);
+        let lines: Vec<&str> = cast.lines().collect();
+        assert_eq!(vec![
+            r#"{"version": 2, "width": 3, "height": 2}"#,
+            r#"[0.000, "o", "\u001b[H\u001b[2Ja\"b\r\n"]"#,
+            r#"[0.500, "o", "\u001b[H\u001b[2Jcd\r\n\\\r\n"]"#,
+        ], lines);
+    }
+
+    #[test]
+    fn aoc22_backends() {
+        let m = "..#\n#..\n...";
+        let rules = parse_rules(PART_2);
+        for &iters in &[0, 1, 100, 10000] {
+            assert_eq!(run_rules::<Map>(m, &rules, iters), run_rules::<DenseMap>(m, &rules, iters));
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn aoc22_bench() {
+        let m = "..#\n#..\n...";
+        let rules = parse_rules(PART_2);
+
+        let now = Instant::now();
+        let hash = run_rules::<Map>(m, &rules, 10000000);
+        println!("hash: {} in {:?}", hash, now.elapsed());
+
+        let now = Instant::now();
+        let dense = run_rules::<DenseMap>(m, &rules, 10000000);
+        println!("dense: {} in {:?}", dense, now.elapsed());
+
+        assert_eq!(hash, dense);
+    }
 }
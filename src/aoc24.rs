@@ -1,10 +1,40 @@
 use regex::Regex;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::str::FromStr;
 
 pub fn run() {
-    let input = fs::read_to_string("day24.txt").unwrap();
-    println!("aoc24-1: {}", run_1(&input));
-    println!("aoc24-2: {:?}", run_2(&input));
+    let args: Vec<String> = env::args().skip(2).collect();
+    let mut file = "day24.txt".to_string();
+    let mut start = 0;
+    let mut objective = None;
+    let mut k = 1;
+    for pair in args.chunks(2) {
+        match (pair[0].as_str(), pair.get(1)) {
+            ("file", Some(f)) => file = f.clone(),
+            ("start", Some(p)) => start = p.parse().unwrap(),
+            ("objective", Some(o)) => objective = Some(o.parse::<Objective>().unwrap()),
+            ("top", Some(n)) => k = n.parse().unwrap(),
+            _ => panic!("usage: 24 [file F] [start PORT] [objective strongest|longest|heaviest:N|strength:N] [top K]"),
+        }
+    }
+
+    let input = fs::read_to_string(file).unwrap();
+    match objective {
+        Some(objective) => {
+            let index = Index::new(input.lines().map(parse).collect());
+            for (i, b) in top_bridges(&index, start, objective, k).iter().enumerate() {
+                println!("{}: {}", i + 1, b);
+            }
+        },
+        None => {
+            let best = bridges(&input);
+            println!("aoc24-1: {}", best.strongest);
+            // length in ports, counting the 0 one at the start, and strength
+            println!("aoc24-2: {:?}", (best.longest.0 + 1, best.longest.1));
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -16,15 +46,11 @@ struct Component {
 impl Component {
     fn new(left: usize, right: usize) -> Self {
         Component {
-            left: left,
-            right: right
+            left,
+            right,
         }
     }
 
-    fn can_follow(&self, val: usize) -> bool {
-        self.left == val || self.right == val
-    }
-
     fn get_other_side(&self, val:usize) -> usize {
         if self.left == val {
             self.right
@@ -47,97 +73,193 @@ fn parse(row: &str) -> Component {
     Component::new(s2i(&c[1]), s2i(&c[2]))
 }
 
-#[derive(Debug, Clone)]
-struct Part {
-    value: usize,
-    children: Vec<Part>
-}
+// Fixed size set of component indices.
+struct BitSet(Vec<u64>);
 
-impl Part {
-    fn new(value: usize) -> Self {
-        Part {
-            value: value,
-            children: Vec::new(),
-        }
-    }   
-}
+impl BitSet {
+    fn new(n: usize) -> Self {
+        BitSet(vec![0; n.div_ceil(64)])
+    }
 
-fn build_bridges(p: &mut Part, components: Vec<Component>) {
-    let p_val = p.value;
-    let childs = components.iter().filter(|&c| c.can_follow(p_val));
+    fn contains(&self, i: usize) -> bool {
+        self.0[i / 64] >> (i % 64) & 1 == 1
+    }
 
-    for c in childs {
-        let mut new_p = Part::new(c.get_other_side(p_val));
-        let comp: Vec<Component> = components.iter().cloned().filter(|&cc| *c != cc).collect();
-        build_bridges(&mut new_p, comp);
-        p.children.push(new_p);
+    fn flip(&mut self, i: usize) {
+        self.0[i / 64] ^= 1 << (i % 64);
     }
 }
 
-fn max_bridge(p: &Part) -> usize {
-    let c_max = match p.children.iter().map(|&ref c| max_bridge(&c)).max() {
-        Some(m) => m,
-        None => 0
-    };
-    if c_max != 0 {
-        2 * p.value + c_max
-    }
-    else {
-        p.value
-    }
+// Components by the ports they have, with identical components linked so
+// only one order of them gets tried.
+struct Index {
+    components: Vec<Component>,
+    by_port: Vec<Vec<usize>>,
+    twin: Vec<Option<usize>>,
 }
 
-fn longest_bridge(p: &Part) -> (usize, usize) {
-    let mut c_max_depth = 0;
-    let mut c_max_bridge = 0;
-    for c in p.children.iter() {
-        let (cd, cb) = longest_bridge(c);
-        if cd > c_max_depth {
-            println!("cd: {} cb: {}", cd, cb);
-            c_max_depth = cd;
-            c_max_bridge = cb;
-        }
-        else if cd == c_max_depth && cb > c_max_bridge {
-            c_max_bridge = cb;
+impl Index {
+    fn new(components: Vec<Component>) -> Self {
+        let ports = components.iter().map(|c| c.left.max(c.right) + 1).max().unwrap_or(0);
+        let mut by_port = vec![Vec::new(); ports];
+        let mut twin = vec![None; components.len()];
+        for (i, c) in components.iter().enumerate() {
+            by_port[c.left].push(i);
+            if c.right != c.left {
+                by_port[c.right].push(i);
+            }
+            twin[i] = (0..i).rev().find(|&j| components[j] == *c);
         }
+        Index { components, by_port, twin }
     }
-    let p_val = if p.children.len() > 0 {
-        p.value * 2
-    }
-    else {
-        p.value
-    };
+}
 
-    (1 + c_max_depth, p_val + c_max_bridge)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Best {
+    strongest: usize,
+    // (components, strength)
+    longest: (usize, usize),
 }
 
-fn run_1(input: &str) -> usize {
-    let components : Vec<Component> = input.lines().map(parse).collect();
+// Calls `visit` with every bridge starting at `port`, the empty one included,
+// and its strength. Extending a bridge is only tried if `visit` returns true.
+fn walk<F>(index: &Index, port: usize, used: &mut BitSet, path: &mut Vec<Component>, strength: usize, visit: &mut F)
+    where F: FnMut(&[Component], usize) -> bool
+{
+    if !visit(path, strength) {
+        return;
+    }
+    let next = match index.by_port.get(port) {
+        Some(next) => next,
+        None => return,
+    };
+    for &i in next {
+        // of identical components, always take the first one left
+        if used.contains(i) || index.twin[i].is_some_and(|j| !used.contains(j)) {
+            continue;
+        }
+        let c = index.components[i];
+        used.flip(i);
+        path.push(c);
+        walk(index, c.get_other_side(port), used, path, strength + c.left + c.right, visit);
+        path.pop();
+        used.flip(i);
+    }
+}
 
-    let mut first = Part::new(0);
+fn search<F: FnMut(&[Component], usize) -> bool>(index: &Index, start: usize, mut visit: F) {
+    let mut used = BitSet::new(index.components.len());
+    let mut path = Vec::with_capacity(index.components.len());
+    walk(index, start, &mut used, &mut path, 0, &mut visit);
+}
 
-    build_bridges(&mut first, components.clone());
+fn bridges(input: &str) -> Best {
+    let index = Index::new(input.lines().map(parse).collect());
+    let mut best = Best::default();
+    search(&index, 0, |path, strength| {
+        best.strongest = best.strongest.max(strength);
+        best.longest = best.longest.max((path.len(), strength));
+        true
+    });
+    best
+}
 
-    // println!("parts: {:?}", first);
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Objective {
+    Strongest,
+    // then strongest
+    Longest,
+    // strongest of at most this many components
+    Heaviest(usize),
+    // longest with exactly this strength
+    Strength(usize),
+}
 
-    max_bridge(&first)
+impl FromStr for Objective {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (name, arg) = match s.find(':') {
+            Some(i) => (&s[..i], s[i + 1..].parse::<usize>().map_err(|_| format!("bad number in {:?}", s))),
+            None => (s, Err(format!("{} needs a number, as in {}:10", s, s))),
+        };
+        match name {
+            "strongest" => Ok(Objective::Strongest),
+            "longest" => Ok(Objective::Longest),
+            "heaviest" => arg.map(Objective::Heaviest),
+            "strength" => arg.map(Objective::Strength),
+            _ => Err(format!("unknown objective {:?}", s)),
+        }
+    }
 }
 
-fn run_2(input: &str) -> (usize, usize) {
-    let components : Vec<Component> = input.lines().map(parse).collect();
+impl Objective {
+    // What to maximise, or None if the bridge doesn't qualify.
+    fn key(&self, len: usize, strength: usize) -> Option<(usize, usize)> {
+        match *self {
+            Objective::Strongest => Some((strength, 0)),
+            Objective::Longest => Some((len, strength)),
+            Objective::Heaviest(n) if len <= n => Some((strength, 0)),
+            Objective::Strength(t) if strength == t => Some((len, 0)),
+            _ => None,
+        }
+    }
 
-    let mut first = Part::new(0);
+    // Whether a longer bridge can still qualify.
+    fn extend(&self, len: usize, strength: usize) -> bool {
+        match *self {
+            Objective::Heaviest(n) => len < n,
+            Objective::Strength(t) => strength <= t,
+            _ => true,
+        }
+    }
+}
 
-    build_bridges(&mut first, components.clone());
+#[derive(Debug, Clone, PartialEq)]
+struct Bridge {
+    components: Vec<Component>,
+    strength: usize,
+}
 
-    // println!("parts: {:?}", first);
+impl fmt::Display for Bridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.components.iter().map(|c| format!("{}/{}", c.left, c.right)).collect();
+        write!(f, "{} (strength {}, length {})", parts.join("--"), self.strength, self.components.len())
+    }
+}
 
-    longest_bridge(&mut first)
+// The k best non-empty bridges from `start`, best first. Of equally good ones
+// those found first win.
+fn top_bridges(index: &Index, start: usize, objective: Objective, k: usize) -> Vec<Bridge> {
+    let mut top: Vec<((usize, usize), Bridge)> = Vec::new();
+    search(index, start, |path, strength| {
+        if path.is_empty() {
+            return true;
+        }
+        if let Some(key) = objective.key(path.len(), strength) {
+            if top.len() < k || top.last().is_some_and(|t| t.0 < key) {
+                let at = top.iter().position(|t| t.0 < key).unwrap_or(top.len());
+                top.insert(at, (key, Bridge { components: path.to_vec(), strength }));
+                top.truncate(k);
+            }
+        }
+        objective.extend(path.len(), strength)
+    });
+    top.into_iter().map(|t| t.1).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn run_1(input: &str) -> usize {
+        bridges(input).strongest
+    }
+
+    fn run_2(input: &str) -> (usize, usize) {
+        let (len, strength) = bridges(input).longest;
+        (len + 1, strength)
+    }
+
     #[test]
     fn aoc24_parse() {
         assert_eq!(Component::new(0,2), parse("0/2"));
@@ -153,4 +275,63 @@ mod tests {
     fn aoc24_2() {
         assert_eq!((5, 19), run_2("0/2\n2/2\n2/3\n3/4\n3/5\n0/1\n10/1\n9/10"));
     }
+
+    #[test]
+    fn aoc24_duplicates() {
+        let input = "0/1\n1/2\n1/2";
+        assert_eq!(7, run_1(input));
+        assert_eq!((4, 7), run_2(input));
+
+        // three identical doubles all fit in
+        let input = "0/3\n3/3\n3/3\n3/3\n3/1";
+        assert_eq!(Best { strongest: 25, longest: (5, 25) }, bridges(input));
+    }
+
+    #[test]
+    fn aoc24_one_pass() {
+        // the longest bridge isn't the strongest one
+        let input = "0/1\n1/1\n1/2\n2/3\n0/20";
+        assert_eq!(Best { strongest: 20, longest: (4, 11) }, bridges(input));
+        assert_eq!(Best::default(), bridges("1/2\n2/3"));
+
+        let mut set = BitSet::new(130);
+        set.flip(129);
+        set.flip(3);
+        assert!(set.contains(129) && set.contains(3) && !set.contains(64));
+        set.flip(3);
+        assert!(!set.contains(3));
+    }
+
+    #[test]
+    fn aoc24_objectives() {
+        let index = Index::new("0/2\n2/2\n2/3\n3/4\n3/5\n0/1\n10/1\n9/10".lines().map(parse).collect());
+        let best = |objective| top_bridges(&index, 0, objective, 1).iter().map(|b| b.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(vec!["0/1--10/1--9/10 (strength 31, length 3)"], best(Objective::Strongest));
+        assert_eq!(vec!["0/2--2/2--2/3--3/5 (strength 19, length 4)"], best(Objective::Longest));
+        assert_eq!(vec!["0/1--10/1 (strength 12, length 2)"], best(Objective::Heaviest(2)));
+        assert_eq!(vec!["0/2--2/2--2/3 (strength 11, length 3)"], best(Objective::Strength(11)));
+        assert_eq!(vec!["0/2--2/3--3/4 (strength 14, length 3)"], best(Objective::Strength(14)));
+        assert!(best(Objective::Strength(5)).is_empty());
+
+        let top: Vec<usize> = top_bridges(&index, 0, Objective::Strongest, 3).iter().map(|b| b.strength).collect();
+        assert_eq!(vec![31, 19, 18], top);
+        // all eleven bridges from the example
+        assert_eq!(11, top_bridges(&index, 0, Objective::Strongest, 100).len());
+
+        let from_ten = top_bridges(&index, 10, Objective::Strongest, 1);
+        assert_eq!("10/1--0/1--0/2--2/2--2/3--3/5", from_ten[0].to_string().split(' ').next().unwrap());
+        assert_eq!(31, from_ten[0].strength);
+    }
+
+    #[test]
+    fn aoc24_parse_objective() {
+        assert_eq!(Ok(Objective::Strongest), "strongest".parse());
+        assert_eq!(Ok(Objective::Longest), "longest".parse());
+        assert_eq!(Ok(Objective::Heaviest(4)), "heaviest:4".parse());
+        assert_eq!(Ok(Objective::Strength(31)), "strength:31".parse());
+        assert!("strength".parse::<Objective>().is_err());
+        assert!("heaviest:x".parse::<Objective>().is_err());
+        assert!("fastest".parse::<Objective>().is_err());
+    }
 }
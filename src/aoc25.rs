@@ -1,21 +1,27 @@
  // #[allow(unused_must_use)]
-use std::collections::{HashSet};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use regex::Regex;
 
-type StateFn = fn (&mut CPU) -> ();
+#[cfg(test)]
+type StateFn = fn (&mut CPU);
 
+#[cfg(test)]
 #[derive(Debug)]
 struct CPU {
     pos: i64,
     state: char,
-    tape: HashSet<i64>,
+    tape: ::std::collections::HashSet<i64>,
 }
 
+#[cfg(test)]
 impl CPU {
-    fn new() -> Self {
+    fn new(state: char) -> Self {
         CPU {
             pos: 0,
-            state: 'A',
-            tape: HashSet::new(),
+            state,
+            tape: ::std::collections::HashSet::new(),
         }
     }
 
@@ -38,8 +44,9 @@ impl CPU {
     }
 }
 
+#[cfg(test)]
 fn run_1(steps: usize, sfn: StateFn) -> usize {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::new('A');
     for _ in 0..steps {
         // println!("=> {:?}", cpu);
         sfn(&mut cpu);
@@ -48,12 +55,573 @@ fn run_1(steps: usize, sfn: StateFn) -> usize {
     cpu.checksum()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Action {
+    write: u8,
+    dir: i64,
+    next: usize,
+}
+
+#[derive(Debug, PartialEq)]
+struct Blueprint {
+    names: Vec<String>,
+    start: usize,
+    steps: usize,
+    symbols: usize,
+    table: Vec<Vec<Action>>,
+}
+
+fn s2i(s: &str) -> usize {
+    s.parse().unwrap()
+}
+
+fn state_idx(names: &mut Vec<String>, name: &str) -> usize {
+    match names.iter().position(|n| n == name) {
+        Some(i) => i,
+        None => {
+            names.push(name.to_string());
+            names.len() - 1
+        }
+    }
+}
+
+fn parse(input: &str) -> Blueprint {
+    let r_begin = Regex::new(r"Begin in state (\w+)\.").unwrap();
+    let r_steps = Regex::new(r"Perform a diagnostic checksum after (\d+) steps\.").unwrap();
+    let r_state = Regex::new(r"In state (\w+):").unwrap();
+    let r_value = Regex::new(r"If the current value is (\d+):").unwrap();
+    let r_write = Regex::new(r"- Write the value (\d+)\.").unwrap();
+    let r_move = Regex::new(r"- Move one slot to the (left|right)\.").unwrap();
+    let r_next = Regex::new(r"- Continue with state (\w+)\.").unwrap();
+
+    let mut names = Vec::new();
+    let mut start = None;
+    let mut steps = None;
+    let mut rules: Vec<HashMap<usize, Action>> = Vec::new();
+    let mut symbols = 1;
+
+    let mut state = None;
+    let mut value = 0;
+    let mut write = 0;
+    let mut dir = 0;
+
+    for (i, row) in input.lines().enumerate() {
+        if let Some(c) = r_begin.captures(row) {
+            start = Some(state_idx(&mut names, &c[1]));
+        }
+        else if let Some(c) = r_steps.captures(row) {
+            steps = Some(s2i(&c[1]));
+        }
+        else if let Some(c) = r_state.captures(row) {
+            state = Some(state_idx(&mut names, &c[1]));
+        }
+        else if let Some(c) = r_value.captures(row) {
+            value = s2i(&c[1]);
+        }
+        else if let Some(c) = r_write.captures(row) {
+            write = s2i(&c[1]);
+        }
+        else if let Some(c) = r_move.captures(row) {
+            dir = if &c[1] == "left" { -1 } else { 1 };
+        }
+        else if let Some(c) = r_next.captures(row) {
+            let s = match state {
+                Some(s) => s,
+                None => panic!("line {}: transition outside of a state", i + 1),
+            };
+            if write > u8::MAX as usize || value > u8::MAX as usize {
+                panic!("line {}: symbol out of range", i + 1);
+            }
+            symbols = symbols.max(value + 1).max(write + 1);
+            let action = Action {
+                write: write as u8,
+                dir,
+                next: state_idx(&mut names, &c[1]),
+            };
+            while rules.len() <= s {
+                rules.push(HashMap::new());
+            }
+            if rules[s].insert(value, action).is_some() {
+                panic!("line {}: state {} has two rules for value {}", i + 1, names[s], value);
+            }
+        }
+        else if !row.trim().is_empty() {
+            panic!("line {}: can't parse {:?}", i + 1, row);
+        }
+    }
+
+    rules.resize(names.len(), HashMap::new());
+    let mut table = Vec::new();
+    for (s, r) in rules.iter().enumerate() {
+        let row = (0..symbols).map(|v| match r.get(&v) {
+            Some(a) => *a,
+            None => panic!("state {} has no rule for value {}", names[s], v),
+        }).collect();
+        table.push(row);
+    }
+
+    Blueprint {
+        names,
+        start: start.expect("missing start state"),
+        steps: steps.expect("missing checksum step count"),
+        symbols,
+        table,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tape {
+    right: Vec<u8>,
+    left: Vec<u8>,
+}
+
+impl Tape {
+    fn get(&self, pos: i64) -> u8 {
+        let v = if pos >= 0 {
+            self.right.get(pos as usize)
+        }
+        else {
+            self.left.get((-pos - 1) as usize)
+        };
+        v.cloned().unwrap_or(0)
+    }
+
+    fn cell(&mut self, pos: i64) -> &mut u8 {
+        let (v, i) = if pos >= 0 {
+            (&mut self.right, pos as usize)
+        }
+        else {
+            (&mut self.left, (-pos - 1) as usize)
+        };
+        if i >= v.len() {
+            v.resize(i + 1, 0);
+        }
+        &mut v[i]
+    }
+
+    fn checksum(&self) -> usize {
+        self.left.iter().chain(self.right.iter()).filter(|&&c| c != 0).count()
+    }
+}
+
+#[derive(Debug)]
+struct Machine {
+    pos: i64,
+    state: usize,
+    steps: usize,
+    tape: Tape,
+}
+
+impl Machine {
+    fn new(state: usize) -> Self {
+        Machine {
+            pos: 0,
+            state,
+            steps: 0,
+            tape: Tape::default(),
+        }
+    }
+
+    fn step(&mut self, bp: &Blueprint) {
+        let cell = self.tape.cell(self.pos);
+        let action = bp.table[self.state][*cell as usize];
+        *cell = action.write;
+        self.pos += action.dir;
+        self.state = action.next;
+        self.steps += 1;
+    }
+
+    fn render(&self, bp: &Blueprint, lo: i64, hi: i64) -> String {
+        let mut s = String::from("...");
+        for p in lo..hi + 1 {
+            if p == self.pos {
+                s += &format!("[{}]", self.tape.get(p));
+            }
+            else {
+                s += &format!(" {} ", self.tape.get(p));
+            }
+        }
+        let when = match self.steps {
+            0 => "before any steps".to_string(),
+            1 => "after 1 step".to_string(),
+            n => format!("after {} steps", n),
+        };
+        s + &format!("... ({}; about to run state {})", when, bp.names[self.state])
+    }
+}
+
+fn trace(bp: &Blueprint, at: &[usize], radius: i64) -> Vec<String> {
+    let mut m = Machine::new(bp.start);
+    let mut rows = Vec::new();
+    let last = at.iter().cloned().max().unwrap_or(0);
+    loop {
+        if at.contains(&m.steps) {
+            rows.push(m.render(bp, m.pos - radius, m.pos + radius));
+        }
+        if m.steps >= last {
+            break;
+        }
+        m.step(bp);
+    }
+    rows
+}
+
+// Space-time diagram of a run, one row per step covering every cell the
+// head visits.
+#[derive(Debug)]
+struct History {
+    symbols: usize,
+    rows: Vec<Vec<u8>>,
+    heads: Vec<i64>,
+}
+
+impl History {
+    fn new(bp: &Blueprint, steps: usize) -> Self {
+        let mut m = Machine::new(bp.start);
+        let (mut lo, mut hi) = (0, 0);
+        for _ in 0..steps {
+            m.step(bp);
+            lo = lo.min(m.pos);
+            hi = hi.max(m.pos);
+        }
+
+        let mut h = History {
+            symbols: bp.symbols,
+            rows: Vec::new(),
+            heads: Vec::new(),
+        };
+        let mut m = Machine::new(bp.start);
+        loop {
+            h.rows.push((lo..hi + 1).map(|p| m.tape.get(p)).collect());
+            h.heads.push(m.pos - lo);
+            if m.steps == steps {
+                break;
+            }
+            m.step(bp);
+        }
+        h
+    }
+
+    fn to_ascii(&self) -> String {
+        let mut s = String::new();
+        for (row, &head) in self.rows.iter().zip(self.heads.iter()) {
+            for (c, &v) in row.iter().enumerate() {
+                s.push(match v {
+                    _ if c as i64 == head => '@',
+                    0 => '.',
+                    1 => '#',
+                    v => (b'0' + v % 10) as char,
+                });
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    fn to_ppm(&self) -> Vec<u8> {
+        let width = self.rows.first().map(|r| r.len()).unwrap_or(0);
+        let mut img = format!("P6\n{} {}\n255\n", width, self.rows.len()).into_bytes();
+        let max = (self.symbols - 1).max(1);
+        for (row, &head) in self.rows.iter().zip(self.heads.iter()) {
+            for (c, &v) in row.iter().enumerate() {
+                let shade = (255 - 255 * v as usize / max) as u8;
+                if c as i64 == head {
+                    img.extend_from_slice(&[255, shade / 2, shade / 2]);
+                }
+                else {
+                    img.extend_from_slice(&[shade, shade, shade]);
+                }
+            }
+        }
+        img
+    }
+}
+
+fn run_blueprint(bp: &Blueprint) -> usize {
+    let mut m = Machine::new(bp.start);
+    for _ in 0..bp.steps {
+        m.step(bp);
+    }
+    m.tape.checksum()
+}
+
+// Blocks of cells are run length encoded on two stacks, one on each side of
+// the head, and the head always sits between two blocks facing one of them.
+// A macro step runs the base machine across a whole block, and when that
+// leaves the head in the same state facing the same way, every copy in the
+// run is handled at once.
+type Run = (Vec<u8>, u64);
+
+const BLANK_RUN: u64 = u64::MAX;
+
+#[derive(Debug, Clone)]
+enum BlockResult {
+    Exit {
+        block: Vec<u8>,
+        state: usize,
+        right: bool,
+        steps: u64,
+    },
+    Loop,
+}
+
+fn block_transition(bp: &Blueprint, state: usize, block: &[u8], from_left: bool) -> BlockResult {
+    let k = block.len();
+    let configs = (bp.table.len() as u64)
+        .saturating_mul((bp.symbols as u64).saturating_pow(k as u32))
+        .saturating_mul(k as u64);
+
+    let mut block = block.to_vec();
+    let mut state = state;
+    let mut i = if from_left { 0 } else { k as i64 - 1 };
+    let mut steps = 0;
+    while steps < configs {
+        let action = bp.table[state][block[i as usize] as usize];
+        block[i as usize] = action.write;
+        i += action.dir;
+        state = action.next;
+        steps += 1;
+        if i < 0 || i >= k as i64 {
+            return BlockResult::Exit {
+                block,
+                state,
+                right: i >= 0,
+                steps,
+            };
+        }
+    }
+    BlockResult::Loop
+}
+
+fn finish_in_block(bp: &Blueprint, state: usize, block: &mut [u8], from_left: bool, steps: u64) {
+    let mut seen = HashMap::new();
+    let mut state = state;
+    let mut i = if from_left { 0 } else { block.len() - 1 };
+    let mut remaining = steps;
+    while remaining > 0 {
+        if let Some(prev) = seen.insert((state, i, block.to_vec()), remaining) {
+            remaining %= prev - remaining;
+            seen.clear();
+            continue;
+        }
+        let action = bp.table[state][block[i] as usize];
+        block[i] = action.write;
+        i = (i as i64 + action.dir) as usize;
+        state = action.next;
+        remaining -= 1;
+    }
+}
+
+#[derive(Debug)]
+struct MacroMachine {
+    state: usize,
+    facing_right: bool,
+    left: Vec<Run>,
+    right: Vec<Run>,
+}
+
+impl MacroMachine {
+    fn new(state: usize) -> Self {
+        MacroMachine {
+            state,
+            facing_right: true,
+            left: Vec::new(),
+            right: Vec::new(),
+        }
+    }
+
+    fn push(stack: &mut Vec<Run>, block: Vec<u8>, count: u64) {
+        if count == 0 || count == BLANK_RUN {
+            return;
+        }
+        if let Some(top) = stack.last_mut() {
+            if top.0 == block {
+                top.1 += count;
+                return;
+            }
+        }
+        stack.push((block, count));
+    }
+
+    fn pop_ahead(&mut self, k: usize) -> Run {
+        let stack = if self.facing_right { &mut self.right } else { &mut self.left };
+        stack.pop().unwrap_or_else(|| (vec![0; k], BLANK_RUN))
+    }
+
+    fn push_ahead(&mut self, block: Vec<u8>, count: u64) {
+        let stack = if self.facing_right { &mut self.right } else { &mut self.left };
+        MacroMachine::push(stack, block, count);
+    }
+
+    fn push_behind(&mut self, block: Vec<u8>, count: u64) {
+        let stack = if self.facing_right { &mut self.left } else { &mut self.right };
+        MacroMachine::push(stack, block, count);
+    }
+
+    fn checksum(&self) -> usize {
+        self.left.iter().chain(self.right.iter())
+            .map(|&(ref b, n)| b.iter().filter(|&&c| c != 0).count() as u64 * n)
+            .sum::<u64>() as usize
+    }
+}
+
+fn run_macro(bp: &Blueprint, k: usize) -> usize {
+    let mut cache = HashMap::new();
+    let mut m = MacroMachine::new(bp.start);
+    let mut remaining = bp.steps as u64;
+
+    while remaining > 0 {
+        let from_left = m.facing_right;
+        let (block, count) = m.pop_ahead(k);
+        let state = m.state;
+        let t = cache.entry((state, block.clone(), from_left))
+            .or_insert_with(|| block_transition(bp, state, &block, from_left))
+            .clone();
+
+        match t {
+            BlockResult::Exit { block: new_block, state, right, steps } if steps <= remaining => {
+                let copies = if state == m.state && right == m.facing_right {
+                    count.min(remaining / steps)
+                }
+                else {
+                    1
+                };
+                if count != BLANK_RUN {
+                    m.push_ahead(block, count - copies);
+                }
+                m.state = state;
+                m.facing_right = right;
+                m.push_behind(new_block, copies);
+                remaining -= copies * steps;
+            },
+            _ => {
+                let mut last = block.clone();
+                finish_in_block(bp, m.state, &mut last, from_left, remaining);
+                if count != BLANK_RUN {
+                    m.push_ahead(block, count - 1);
+                }
+                m.push_ahead(last, 1);
+                remaining = 0;
+            }
+        }
+    }
+
+    m.checksum()
+}
+
+fn emit_puzzle(bp: &Blueprint) -> String {
+    let mut s = format!("Begin in state {}.\n", bp.names[bp.start]);
+    s += &format!("Perform a diagnostic checksum after {} steps.\n", bp.steps);
+    for (state, row) in bp.table.iter().enumerate() {
+        s += &format!("\nIn state {}:\n", bp.names[state]);
+        for (value, a) in row.iter().enumerate() {
+            s += &format!("  If the current value is {}:\n", value);
+            s += &format!("    - Write the value {}.\n", a.write);
+            s += &format!("    - Move one slot to the {}.\n", if a.dir < 0 { "left" } else { "right" });
+            s += &format!("    - Continue with state {}.\n", bp.names[a.next]);
+        }
+    }
+    s
+}
+
+// The compact notation names states by position, starting from 'A' for the
+// start state, so the states are renumbered with the start state first.
+fn emit_compact(bp: &Blueprint) -> String {
+    if bp.table.len() > 26 {
+        panic!("compact notation supports at most 26 states, got {}", bp.table.len());
+    }
+    let mut order: Vec<usize> = (0..bp.table.len()).collect();
+    order.retain(|&s| s != bp.start);
+    order.insert(0, bp.start);
+    let letter = |s: usize| (b'A' + order.iter().position(|&o| o == s).unwrap() as u8) as char;
+
+    let groups: Vec<String> = order.iter().map(|&state| {
+        bp.table[state].iter().map(|a| {
+            format!("{}{}{}", a.write, if a.dir < 0 { 'L' } else { 'R' }, letter(a.next))
+        }).collect()
+    }).collect();
+    groups.join("_")
+}
+
+fn emit_rust(bp: &Blueprint, name: &str) -> String {
+    if bp.symbols != 2 {
+        panic!("CPU only supports two symbols, got {}", bp.symbols);
+    }
+    let mut s = format!("fn {}(c: &mut CPU) {{\n    match c.state {{\n", name);
+    for (state, row) in bp.table.iter().enumerate() {
+        if bp.names[state].chars().count() != 1 {
+            panic!("CPU states are single characters, got {:?}", bp.names[state]);
+        }
+        s += &format!("        '{}' => {{\n", bp.names[state]);
+        for (value, a) in row.iter().enumerate().rev() {
+            s += if value == 1 { "            if c.cur_val() {\n" } else { "            else {\n" };
+            s += if a.write == 1 { "                c.set();\n" } else { "                c.reset();\n" };
+            s += if a.dir < 0 { "                c.pos -= 1;\n" } else { "                c.pos += 1;\n" };
+            s += &format!("                c.state = '{}';\n", bp.names[a.next]);
+            s += "            }\n";
+        }
+        s += "        },\n";
+    }
+    s += "        s => panic!(\"Unknown state: {:?}\", s)\n    }\n}\n";
+    s
+}
+
+const MACRO_STEPS: usize = 1_000_000_000;
+const MACRO_BLOCK: usize = 6;
+
 pub fn run() {
-    let d1 = run_1(12919244, day_1);
-    println!("t1: {}", d1); 
+    let input = fs::read_to_string("day25.txt").unwrap();
+    let bp = parse(&input);
+
+    let args: Vec<String> = env::args().skip(2).collect();
+    let usage = "usage: 25 [tape STEP,STEP,...] [history STEPS FILE] [emit puzzle|compact|rust]";
+    match args.first().map(|s| s.as_str()) {
+        Some("tape") => {
+            let at: Vec<usize> = match args.get(1) {
+                Some(steps) => steps.split(',').map(s2i).collect(),
+                None => return println!("{}", usage),
+            };
+            for row in trace(&bp, &at, 5) {
+                println!("{}", row);
+            }
+            return;
+        },
+        Some("history") => {
+            let (steps, file) = match (args.get(1), args.get(2)) {
+                (Some(steps), Some(file)) => (s2i(steps), file),
+                _ => return println!("{}", usage),
+            };
+            let h = History::new(&bp, steps);
+            if file.ends_with(".ppm") {
+                fs::write(file, h.to_ppm()).unwrap();
+            }
+            else {
+                fs::write(file, h.to_ascii()).unwrap();
+            }
+            return;
+        },
+        Some("emit") => {
+            match args.get(1).map(|s| s.as_str()) {
+                Some("compact") => println!("{}", emit_compact(&bp)),
+                Some("rust") => print!("{}", emit_rust(&bp, "day_1")),
+                _ => print!("{}", emit_puzzle(&bp)),
+            }
+            return;
+        },
+        _ => ()
+    }
+
+    let checksum = if bp.steps >= MACRO_STEPS {
+        run_macro(&bp, MACRO_BLOCK)
+    }
+    else {
+        run_blueprint(&bp)
+    };
+    println!("t1: {}", checksum);
 }
 
-fn day_1(c: &mut CPU) -> () {
+#[cfg(test)]
+fn day_1(c: &mut CPU) {
     match c.state {
         'A' => {
             if c.cur_val() {
@@ -134,7 +702,33 @@ fn day_1(c: &mut CPU) -> () {
 #[cfg(test)]
 mod tests {
     use super::*;
-    fn day_1_sfn(c: &mut CPU) -> () {
+    use std::time::Instant;
+
+    const EXAMPLE: &str = r"Begin in state A.
+Perform a diagnostic checksum after 6 steps.
+
+In state A:
+  If the current value is 0:
+    - Write the value 1.
+    - Move one slot to the right.
+    - Continue with state B.
+  If the current value is 1:
+    - Write the value 0.
+    - Move one slot to the left.
+    - Continue with state B.
+
+In state B:
+  If the current value is 0:
+    - Write the value 1.
+    - Move one slot to the left.
+    - Continue with state A.
+  If the current value is 1:
+    - Write the value 1.
+    - Move one slot to the right.
+    - Continue with state A.
+";
+
+    fn day_1_sfn(c: &mut CPU) {
         match c.state {
             'A' => {
                 if c.cur_val() {
@@ -167,4 +761,233 @@ mod tests {
     fn day_1() {
         assert_eq!(3, run_1(6, day_1_sfn));
     }
+
+    #[test]
+    fn aoc25_parse() {
+        let bp = parse(EXAMPLE);
+        assert_eq!(vec!["A", "B"], bp.names);
+        assert_eq!(0, bp.start);
+        assert_eq!(6, bp.steps);
+        assert_eq!(2, bp.symbols);
+        assert_eq!(2, bp.table.len());
+        assert_eq!(Action { write: 0, dir: -1, next: 1 }, bp.table[0][1]);
+        assert_eq!(Action { write: 1, dir: -1, next: 0 }, bp.table[1][0]);
+    }
+
+    #[test]
+    fn aoc25_three_symbols() {
+        let input = r"Begin in state Start.
+Perform a diagnostic checksum after 4 steps.
+
+In state Start:
+  If the current value is 0:
+    - Write the value 1.
+    - Move one slot to the right.
+    - Continue with state Other.
+  If the current value is 1:
+    - Write the value 2.
+    - Move one slot to the left.
+    - Continue with state Other.
+  If the current value is 2:
+    - Write the value 0.
+    - Move one slot to the right.
+    - Continue with state Start.
+
+In state Other:
+  If the current value is 0:
+    - Write the value 2.
+    - Move one slot to the left.
+    - Continue with state Start.
+  If the current value is 1:
+    - Write the value 2.
+    - Move one slot to the right.
+    - Continue with state Start.
+  If the current value is 2:
+    - Write the value 1.
+    - Move one slot to the right.
+    - Continue with state Other.
+";
+        let bp = parse(input);
+        assert_eq!(3, bp.symbols);
+        let mut m = Machine::new(bp.start);
+        for _ in 0..bp.steps {
+            m.step(&bp);
+        }
+        assert_eq!(-2, m.pos);
+        assert_eq!("Start", bp.names[m.state]);
+        assert_eq!(vec![0, 2, 2, 2], (-2..2).map(|p| *m.tape.cell(p)).collect::<Vec<u8>>());
+        assert_eq!(3, m.tape.checksum());
+    }
+
+    #[test]
+    #[should_panic(expected = "state B has no rule for value 1")]
+    fn aoc25_missing_rule() {
+        let end = EXAMPLE.rfind("  If the current value is 1").unwrap();
+        parse(&EXAMPLE[..end]);
+    }
+
+    #[test]
+    fn aoc25_blueprint() {
+        assert_eq!(3, run_blueprint(&parse(EXAMPLE)));
+    }
+
+    #[test]
+    fn aoc25_blueprint_matches_day_1() {
+        let mut bp = parse(&fs::read_to_string("day25.txt").unwrap());
+        bp.steps = 100000;
+        assert_eq!(run_1(bp.steps, super::day_1), run_blueprint(&bp));
+    }
+
+    #[test]
+    #[ignore]
+    fn aoc25_bench() {
+        let bp = parse(&fs::read_to_string("day25.txt").unwrap());
+
+        let now = Instant::now();
+        let hand = run_1(bp.steps, super::day_1);
+        println!("run_1: {} in {:?}", hand, now.elapsed());
+
+        let now = Instant::now();
+        let table = run_blueprint(&bp);
+        println!("run_blueprint: {} in {:?}", table, now.elapsed());
+
+        assert_eq!(hand, table);
+
+        for k in 1..9 {
+            let now = Instant::now();
+            let mac = run_macro(&bp, k);
+            println!("run_macro({}): {} in {:?}", k, mac, now.elapsed());
+            assert_eq!(hand, mac);
+        }
+    }
+
+    const SWEEPER: &str = r"Begin in state A.
+Perform a diagnostic checksum after 1000000 steps.
+
+In state A:
+  If the current value is 0:
+    - Write the value 1.
+    - Move one slot to the right.
+    - Continue with state B.
+  If the current value is 1:
+    - Write the value 1.
+    - Move one slot to the left.
+    - Continue with state A.
+
+In state B:
+  If the current value is 0:
+    - Write the value 1.
+    - Move one slot to the left.
+    - Continue with state A.
+  If the current value is 1:
+    - Write the value 1.
+    - Move one slot to the right.
+    - Continue with state B.
+";
+
+    const BOUNCER: &str = r"Begin in state A.
+Perform a diagnostic checksum after 1000000 steps.
+
+In state A:
+  If the current value is 0:
+    - Write the value 1.
+    - Move one slot to the right.
+    - Continue with state B.
+  If the current value is 1:
+    - Write the value 0.
+    - Move one slot to the right.
+    - Continue with state B.
+
+In state B:
+  If the current value is 0:
+    - Write the value 1.
+    - Move one slot to the left.
+    - Continue with state A.
+  If the current value is 1:
+    - Write the value 0.
+    - Move one slot to the left.
+    - Continue with state A.
+";
+
+    #[test]
+    fn aoc25_macro() {
+        for input in &[EXAMPLE, SWEEPER, BOUNCER] {
+            let mut bp = parse(input);
+            for &steps in &[0, 1, 7, 100, 12345, 1000000] {
+                bp.steps = steps;
+                let expected = run_blueprint(&bp);
+                for k in 1..6 {
+                    assert_eq!(expected, run_macro(&bp, k), "{} steps, k = {}", steps, k);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn aoc25_macro_billions() {
+        let mut bp = parse(SWEEPER);
+        bp.steps = 10_000_000_000;
+        // every sweep adds a one at the edge, so n ones take about n^2/2 steps
+        for k in 1..4 {
+            assert_eq!(141421, run_macro(&bp, k));
+        }
+    }
+
+    #[test]
+    fn aoc25_render() {
+        let bp = parse(EXAMPLE);
+        let mut m = Machine::new(bp.start);
+        assert_eq!("... 0  0  0 [0] 0  0 ... (before any steps; about to run state A)", m.render(&bp, -3, 2));
+        m.step(&bp);
+        assert_eq!("... 0  0  0  1 [0] 0 ... (after 1 step; about to run state B)", m.render(&bp, -3, 2));
+        for _ in 0..5 {
+            m.step(&bp);
+        }
+        assert_eq!("... 0  1  1 [0] 1  0 ... (after 6 steps; about to run state A)", m.render(&bp, -3, 2));
+
+        let rows = trace(&bp, &[2, 4], 1);
+        assert_eq!(vec![
+            "... 0 [1] 1 ... (after 2 steps; about to run state A)",
+            "... 0 [0] 1 ... (after 4 steps; about to run state A)",
+        ], rows);
+    }
+
+    #[test]
+    fn aoc25_history() {
+        let h = History::new(&parse(EXAMPLE), 6);
+        assert_eq!("..@.\n..#@\n..@#\n.@.#\n@#.#\n#@.#\n##@#\n", h.to_ascii());
+
+        let img = h.to_ppm();
+        assert!(img.starts_with(b"P6\n4 7\n255\n"));
+        assert_eq!(b"P6\n4 7\n255\n".len() + 4 * 7 * 3, img.len());
+    }
+
+    #[test]
+    fn aoc25_emit_puzzle() {
+        let input = fs::read_to_string("day25.txt").unwrap();
+        assert_eq!(input, emit_puzzle(&parse(&input)));
+        assert_eq!(EXAMPLE, emit_puzzle(&parse(EXAMPLE)));
+    }
+
+    #[test]
+    fn aoc25_emit_compact() {
+        assert_eq!("1RB0LB_1LA1RA", emit_compact(&parse(EXAMPLE)));
+        assert_eq!("1RB1LA_1LA1RB", emit_compact(&parse(SWEEPER)));
+
+        let input = fs::read_to_string("day25.txt").unwrap();
+        assert_eq!("1RB0LC_1LA1RD_1RA0LE_1RA0RB_1LF1LC_1RD1RA", emit_compact(&parse(&input)));
+
+        let reordered = EXAMPLE.replace("Begin in state A.", "Begin in state B.");
+        assert_eq!("1LB1RB_1RA0LA", emit_compact(&parse(&reordered)));
+    }
+
+    #[test]
+    fn aoc25_emit_rust() {
+        let source = fs::read_to_string("src/aoc25.rs").unwrap();
+        let start = source.find("\nfn day_1(").unwrap() + 1;
+        let end = start + source[start..].find("\n}\n").unwrap() + 3;
+
+        let bp = parse(&fs::read_to_string("day25.txt").unwrap());
+        assert_eq!(&source[start..end], emit_rust(&bp, "day_1"));
+    }
 }
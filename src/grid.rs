@@ -0,0 +1,364 @@
+// Shared helpers for the days that walk around on a 2D map. Not every day
+// uses every helper.
+
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right
+}
+
+impl Direction {
+    pub fn all() -> [Direction; 4] {
+        [Direction::Up, Direction::Right, Direction::Down, Direction::Left]
+    }
+
+    // (row, col)
+    pub fn delta(&self) -> (isize, isize) {
+        match *self {
+            Direction::Down => (1, 0),
+            Direction::Up => (-1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+
+    pub fn turn_left(&self) -> Self {
+        match *self {
+            Direction::Up => Direction::Left,
+            Direction::Down => Direction::Right,
+            Direction::Left => Direction::Down,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turn_right(&self) -> Self {
+        match *self {
+            Direction::Up => Direction::Right,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+            Direction::Right => Direction::Down,
+        }
+    }
+
+    pub fn turn_around(&self) -> Self {
+        match *self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pos {
+    pub row: isize,
+    pub col: isize,
+}
+
+impl Pos {
+    pub fn new(row: isize, col: isize) -> Self {
+        Pos {
+            row,
+            col,
+        }
+    }
+
+    pub fn step(&self, dir: Direction) -> Self {
+        let (dr, dc) = dir.delta();
+        Pos::new(self.row + dr, self.col + dc)
+    }
+}
+
+// Number of rows and the length of the longest row.
+pub fn text_size(s: &str) -> (usize, usize) {
+    let height = s.lines().count();
+    let width = s.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+    (height, width)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(height: usize, width: usize, fill: T) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+
+    // Short rows are padded with `fill`.
+    pub fn parse<F: Fn(char) -> T>(s: &str, fill: T, f: F) -> Self {
+        let (height, width) = text_size(s);
+        let mut g = Grid::new(height, width, fill);
+        for (r, row) in s.lines().enumerate() {
+            for (c, ch) in row.chars().enumerate() {
+                g.cells[r * width + c] = f(ch);
+            }
+        }
+        g
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, p: Pos) -> bool {
+        p.row >= 0 && p.col >= 0 && (p.row as usize) < self.height && (p.col as usize) < self.width
+    }
+
+    pub fn get(&self, p: Pos) -> Option<&T> {
+        if self.contains(p) {
+            Some(&self.cells[p.row as usize * self.width + p.col as usize])
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, p: Pos) -> Option<&mut T> {
+        if self.contains(p) {
+            Some(&mut self.cells[p.row as usize * self.width + p.col as usize])
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn positions(&self) -> Vec<Pos> {
+        let mut v = Vec::with_capacity(self.cells.len());
+        for r in 0..self.height {
+            for c in 0..self.width {
+                v.push(Pos::new(r as isize, c as isize));
+            }
+        }
+        v
+    }
+}
+
+impl<T: Clone> Index<Pos> for Grid<T> {
+    type Output = T;
+    fn index(&self, p: Pos) -> &T {
+        match self.get(p) {
+            Some(v) => v,
+            None => panic!("{:?} is outside the {}x{} grid", p, self.height, self.width),
+        }
+    }
+}
+
+impl<T: Clone> IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, p: Pos) -> &mut T {
+        let (h, w) = (self.height, self.width);
+        match self.get_mut(p) {
+            Some(v) => v,
+            None => panic!("{:?} is outside the {}x{} grid", p, h, w),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Pos, T>,
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> Self {
+        SparseGrid {
+            cells: HashMap::new(),
+        }
+    }
+
+    // Characters mapped to None are left out.
+    pub fn parse<F: Fn(char) -> Option<T>>(s: &str, f: F) -> Self {
+        let mut g = SparseGrid::new();
+        for (r, row) in s.lines().enumerate() {
+            for (c, ch) in row.chars().enumerate() {
+                if let Some(v) = f(ch) {
+                    g.insert(Pos::new(r as isize, c as isize), v);
+                }
+            }
+        }
+        g
+    }
+
+    pub fn get(&self, p: &Pos) -> Option<&T> {
+        self.cells.get(p)
+    }
+
+    pub fn insert(&mut self, p: Pos, v: T) -> Option<T> {
+        self.cells.insert(p, v)
+    }
+
+    pub fn remove(&mut self, p: &Pos) -> Option<T> {
+        self.cells.remove(p)
+    }
+
+    pub fn iter(&self) -> ::std::collections::hash_map::Iter<'_, Pos, T> {
+        self.cells.iter()
+    }
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        SparseGrid::new()
+    }
+}
+
+// Dense grid without fixed bounds. Reads outside the allocated area give
+// `fill`, writes grow the area, at least doubling it in the direction of the
+// write so a wandering writer only reallocates now and then.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrowGrid<T> {
+    origin: Pos,
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+    fill: T,
+}
+
+impl<T: Clone> GrowGrid<T> {
+    pub fn new(fill: T) -> Self {
+        GrowGrid {
+            origin: Pos::new(0, 0),
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+            fill,
+        }
+    }
+
+    fn index(&self, p: Pos) -> Option<usize> {
+        let r = p.row - self.origin.row;
+        let c = p.col - self.origin.col;
+        if r >= 0 && c >= 0 && (r as usize) < self.height && (c as usize) < self.width {
+            Some(r as usize * self.width + c as usize)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn get(&self, p: Pos) -> &T {
+        match self.index(p) {
+            Some(i) => &self.cells[i],
+            None => &self.fill,
+        }
+    }
+
+    pub fn get_mut(&mut self, p: Pos) -> &mut T {
+        if self.index(p).is_none() {
+            self.grow(p);
+        }
+        let i = self.index(p).unwrap();
+        &mut self.cells[i]
+    }
+
+    fn grow(&mut self, p: Pos) {
+        let (w, h) = (self.width as isize, self.height as isize);
+        let (mut lo, mut hi) = if self.cells.is_empty() {
+            (p, p)
+        }
+        else {
+            (self.origin, Pos::new(self.origin.row + h - 1, self.origin.col + w - 1))
+        };
+        if p.row < lo.row {
+            lo.row = p.row.min(lo.row - h);
+        }
+        if p.row > hi.row {
+            hi.row = p.row.max(hi.row + h);
+        }
+        if p.col < lo.col {
+            lo.col = p.col.min(lo.col - w);
+        }
+        if p.col > hi.col {
+            hi.col = p.col.max(hi.col + w);
+        }
+
+        let width = (hi.col - lo.col + 1) as usize;
+        let height = (hi.row - lo.row + 1) as usize;
+        let mut cells = vec![self.fill.clone(); width * height];
+        for r in 0..self.height {
+            let dst = (r as isize + self.origin.row - lo.row) as usize * width
+                + (self.origin.col - lo.col) as usize;
+            cells[dst..dst + self.width].clone_from_slice(&self.cells[r * self.width..(r + 1) * self.width]);
+        }
+
+        self.origin = lo;
+        self.width = width;
+        self.height = height;
+        self.cells = cells;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_turns() {
+        for &d in Direction::all().iter() {
+            assert_eq!(d, d.turn_left().turn_right());
+            assert_eq!(d.turn_around(), d.turn_left().turn_left());
+            assert_eq!(d.turn_right(), d.turn_around().turn_left());
+        }
+        assert_eq!(Pos::new(-1, 0), Pos::new(0, 0).step(Direction::Up));
+        assert_eq!(Pos::new(0, 1), Pos::new(0, 0).step(Direction::Right));
+    }
+
+    #[test]
+    fn grid_dense() {
+        let g = Grid::parse("ab\nc", ' ', |c| c);
+        assert_eq!((2, 2), (g.height(), g.width()));
+        assert_eq!(Some(&'b'), g.get(Pos::new(0, 1)));
+        assert_eq!(Some(&' '), g.get(Pos::new(1, 1)));
+        assert_eq!(None, g.get(Pos::new(2, 0)));
+        assert_eq!(None, g.get(Pos::new(0, -1)));
+        assert_eq!(4, g.positions().len());
+    }
+
+    #[test]
+    fn grid_sparse() {
+        let mut g = SparseGrid::parse("..#\n#..", |c| if c == '#' { Some(c) } else { None });
+        assert_eq!(2, g.iter().count());
+        assert_eq!(Some(&'#'), g.get(&Pos::new(0, 2)));
+        g.insert(Pos::new(-1, 1), 'x');
+        assert_eq!(Some(&'x'), g.get(&Pos::new(-1, 1)));
+        g.remove(&Pos::new(-1, 1));
+        assert_eq!(None, g.get(&Pos::new(-1, 1)));
+    }
+
+    #[test]
+    fn grid_grow() {
+        let mut g = GrowGrid::new('.');
+        assert_eq!(&'.', g.get(Pos::new(5, 5)));
+
+        *g.get_mut(Pos::new(0, 0)) = 'a';
+        *g.get_mut(Pos::new(-1, 2)) = 'b';
+        *g.get_mut(Pos::new(3, -4)) = 'c';
+        assert_eq!(&'a', g.get(Pos::new(0, 0)));
+        assert_eq!(&'b', g.get(Pos::new(-1, 2)));
+        assert_eq!(&'c', g.get(Pos::new(3, -4)));
+        assert_eq!(&'.', g.get(Pos::new(1, 1)));
+
+        for i in 0..100 {
+            *g.get_mut(Pos::new(i, -i)) = 'x';
+        }
+        assert_eq!(&'x', g.get(Pos::new(99, -99)));
+        assert_eq!(&'c', g.get(Pos::new(3, -4)));
+    }
+}
@@ -3,6 +3,8 @@ extern crate regex;
 
 use std::env;
 
+mod grid;
+
 mod aoc1;
 mod aoc2;
 mod aoc16;